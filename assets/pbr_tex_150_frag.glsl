@@ -14,6 +14,14 @@ uniform float opacity;
 
 uniform sampler2D t_color;
 
+// planar reflection, drawn for entities tagged "mirror"
+uniform vec2 screen_size;
+uniform int has_reflection;
+// view-space normal of the mirror plane
+uniform vec3 reflection_normal;
+uniform float reflection_distortion;
+uniform sampler2D t_reflection;
+
 // defines
 #define PI 3.14159265359
 #define PI2 6.28318530718
@@ -241,6 +249,15 @@ void main() {
     RE_Direct(directLight, geometry, material, reflectedLight);
   }
 
+  if (has_reflection != 0) {
+    // only the tilt away from the plane distorts, so flat mirrors stay sharp
+    vec3 tilt = geometry.normal - reflection_normal * dot(geometry.normal, reflection_normal);
+    vec2 uv = clamp(gl_FragCoord.xy / screen_size + tilt.xy * reflection_distortion, 0.0, 1.0);
+    vec4 mirrored = texture(t_reflection, uv);
+    vec3 F = F_Schlick(material.specularColor, -geometry.normal, geometry.viewDir);
+    reflectedLight.indirectSpecular += mirrored.rgb * mirrored.a * F * (1.0 - roughness);
+  }
+
   vec3 outgoingLight = emissive + reflectedLight.directDiffuse + reflectedLight.directSpecular + reflectedLight.indirectDiffuse + reflectedLight.indirectSpecular;
 
   f_color = vec4(outgoingLight, opacity);
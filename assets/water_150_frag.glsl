@@ -0,0 +1,114 @@
+#version 150 core
+in vec3 v_normal;
+in vec3 v_world_normal;
+in vec3 v_view_position;
+in vec3 v_world_position;
+in float v_slope;
+out vec4 f_color;
+
+uniform mat4 inverse_view;
+uniform mat4 inverse_projection;
+uniform vec2 screen_size;
+uniform float time;
+
+uniform vec3 scatter_color;
+uniform vec3 absorption;
+uniform vec3 sky_color;
+uniform vec3 horizon_color;
+uniform float refraction;
+uniform float roughness;
+uniform float foam;
+uniform float foam_slope;
+uniform float shore_foam;
+uniform float caustics;
+uniform float reflection;
+uniform float reflection_distortion;
+
+// world space, towards the light
+uniform vec3 sun_direction;
+uniform vec3 sun_color;
+
+uniform int has_scene;
+uniform sampler2D t_scene;
+uniform sampler2D t_depth;
+uniform int has_reflection;
+uniform sampler2D t_reflection;
+
+#define PI 3.14159265359
+// reflectance of water at normal incidence
+#define F0 0.02
+
+vec3 sky(vec3 direction) {
+  return mix(horizon_color, sky_color, sqrt(clamp(direction.y, 0.0, 1.0)));
+}
+
+// view-space position of a depth buffer sample
+vec3 viewPosition(vec2 uv, float depth) {
+  vec4 p = inverse_projection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+  return p.xyz / p.w;
+}
+
+// two drifting interference patterns, bright where both peak; stands in for
+// light focused by the waves onto what's below
+float causticPattern(vec2 p) {
+  float a = sin(p.x * 9.0 + time * 1.3) + sin(p.y * 11.0 - time * 1.1);
+  float b = sin((p.x + p.y) * 7.0 - time * 0.9) + sin((p.x - p.y) * 8.0 + time * 1.7);
+  return pow(clamp((a + b) * 0.125 + 0.5, 0.0, 1.0), 6.0);
+}
+
+void main() {
+  vec3 N = normalize(v_world_normal);
+  vec3 eye = (inverse_view * vec4(0.0, 0.0, 0.0, 1.0)).xyz;
+  vec3 V = normalize(eye - v_world_position);
+  float dotNV = clamp(dot(N, V), 0.0, 1.0);
+  float fresnel = F0 + (1.0 - F0) * pow(1.0 - dotNV, 5.0);
+
+  // environment and sun highlight
+  vec3 reflected = sky(reflect(-V, N));
+  // the mirrored scene where it was drawn, the sky elsewhere
+  if (has_reflection != 0) {
+    vec2 uv = clamp(gl_FragCoord.xy / screen_size + N.xz * reflection_distortion, 0.0, 1.0);
+    vec4 mirrored = texture(t_reflection, uv);
+    reflected = mix(reflected, mirrored.rgb, mirrored.a * reflection);
+  }
+  vec3 H = normalize(V + sun_direction);
+  float a = max(roughness * roughness, 1e-3);
+  float shininess = 2.0 / (a * a) - 2.0;
+  reflected += sun_color * pow(clamp(dot(N, H), 0.0, 1.0), shininess) * (shininess + 8.0) / (8.0 * PI) * step(0.0, dot(N, sun_direction));
+
+  // the scene behind, absorbed and scattered along the path through the water
+  vec3 refracted = scatter_color;
+  float waterDepth = 1e6;
+  if (has_scene != 0) {
+    vec2 uv = gl_FragCoord.xy / screen_size;
+    vec2 refractedUv = clamp(uv + normalize(v_normal).xy * refraction, 0.0, 1.0);
+    vec3 behind = viewPosition(refractedUv, texture(t_depth, refractedUv).r);
+    // the offset sample is in front of the water
+    if (behind.z > v_view_position.z) {
+      refractedUv = uv;
+      behind = viewPosition(uv, texture(t_depth, uv).r);
+    }
+    waterDepth = max(length(behind) - length(v_view_position), 0.0);
+    vec3 scene = texture(t_scene, refractedUv).rgb;
+
+    if (caustics > 0.0) {
+      vec3 ground = (inverse_view * vec4(behind, 1.0)).xyz;
+      float below = v_world_position.y - ground.y;
+      scene *= 1.0 + caustics * causticPattern(ground.xz) * exp(-below) * step(0.0, below);
+    }
+
+    vec3 transmittance = exp(-absorption * waterDepth);
+    refracted = scene * transmittance + scatter_color * (1.0 - transmittance);
+  }
+
+  vec3 color = mix(refracted, reflected, fresnel);
+
+  // breaking crests, and a line where the water meets objects
+  float amount = smoothstep(foam_slope, foam_slope * 2.0, v_slope);
+  if (shore_foam > 0.0) {
+    amount = max(amount, 1.0 - smoothstep(0.0, shore_foam, waterDepth));
+  }
+  color = mix(color, vec3(1.0), clamp(amount * foam, 0.0, 1.0));
+
+  f_color = vec4(color, 1.0);
+}
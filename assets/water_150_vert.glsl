@@ -0,0 +1,24 @@
+#version 150 core
+in vec3 position;
+in vec3 normal;
+in vec2 texture;
+out vec3 v_normal;
+out vec3 v_world_normal;
+out vec3 v_view_position;
+out vec3 v_world_position;
+out float v_slope;
+uniform mat4 model_matrix;
+uniform mat4 model_view_matrix;
+uniform mat4 projection_matrix;
+uniform mat3 normal_matrix;
+void main() {
+    // fluid normals are stored pointing down
+    vec3 up = vec3(normal.x, -normal.y, normal.z);
+    v_normal = normal_matrix * up;
+    v_world_normal = mat3(model_matrix) * up;
+    v_slope = length(up.xz) / max(up.y, 1e-3);
+    vec4 mv_position = model_view_matrix * vec4(position, 1.0);
+    gl_Position = projection_matrix * mv_position;
+    v_view_position = mv_position.xyz;
+    v_world_position = (model_matrix * vec4(position, 1.0)).xyz;
+}
@@ -0,0 +1,68 @@
+// the only sketch in its registry: water with a light that pulses and drifts
+// using keyframe animation, and a second light driven by a simulation closure
+extern crate cgmath;
+extern crate mg_rs_piston;
+
+use mg_rs_piston::animation::{Animation, Channel, Easing, Repeat, Track};
+use mg_rs_piston::app;
+use mg_rs_piston::camera::CameraRig;
+use mg_rs_piston::component::{Light, Simulation};
+use mg_rs_piston::entity::Entity;
+use mg_rs_piston::entity::entity_fluid::FluidEntity;
+use mg_rs_piston::random::Random;
+use mg_rs_piston::render::RenderContext;
+use mg_rs_piston::sketch::{Sketch, SketchRegistry};
+use mg_rs_piston::transform::Transform;
+use mg_rs_piston::world::{BasicWorld, World};
+
+struct PulsingLight;
+
+impl Sketch for PulsingLight {
+    fn setup(&mut self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig, random: &Random) {
+        ctx.background = [0.02, 0.02, 0.05, 1.0];
+        rig.look_from(cgmath::Vector3::new(0.0, 1.2, 2.0), cgmath::Vector3::new(0.0, 0.0, 0.0));
+
+        let mut water = FluidEntity::new(ctx, 200, 200, 2.0);
+        water.randomize(&mut random.stream("water"));
+        world.add_entity(Box::new(water));
+
+        let pulse = world.create();
+        world.transforms.insert(pulse, Transform::identity());
+        world.lights.insert(pulse, Light::Point {
+            color: [1.0, 0.5, 0.2],
+            distance: 3.0,
+            decay: 2.0,
+        });
+        world.animations.insert(pulse, Animation::new(Repeat::PingPong)
+            .with(Channel::Translation(Track::tween(
+                cgmath::Vector3::new(-0.8, 0.3, 0.0),
+                cgmath::Vector3::new(0.8, 0.3, 0.0),
+                4.0,
+                Easing::SineInOut,
+            )))
+            .with(Channel::LightColor(Track::new()
+                .key(0.0, [1.0, 0.5, 0.2], Easing::QuadInOut)
+                .key(2.0, [0.2, 0.5, 1.0], Easing::QuadInOut)
+                .key(4.0, [1.0, 0.5, 0.2], Easing::Linear))));
+
+        let circling = world.create();
+        world.transforms.insert(circling, Transform::identity());
+        world.lights.insert(circling, Light::Point {
+            color: [0.6, 1.0, 0.6],
+            distance: 2.0,
+            decay: 2.0,
+        });
+        let mut angle = 0.0f32;
+        let orbit = move |transform: &mut Transform, dt: f64| {
+            angle += dt as f32;
+            transform.translation = cgmath::Vector3::new(angle.cos(), 0.5, angle.sin());
+        };
+        world.simulations.insert(circling, Box::new(orbit) as Box<Simulation>);
+    }
+}
+
+fn main() {
+    let mut registry = SketchRegistry::new();
+    registry.register("pulsing_light", || Box::new(PulsingLight));
+    app::run(registry);
+}
@@ -0,0 +1,49 @@
+// a sketch built from library pieces: an imported model lit by two lights,
+// registered next to the built-in sketches
+extern crate cgmath;
+extern crate mg_rs_piston;
+
+use mg_rs_piston::app;
+use mg_rs_piston::camera::CameraRig;
+use mg_rs_piston::component::Light;
+use mg_rs_piston::entity::entity_obj::EntityObj;
+use mg_rs_piston::random::Random;
+use mg_rs_piston::render::RenderContext;
+use mg_rs_piston::sketch::{Sketch, SketchRegistry};
+use mg_rs_piston::transform::Transform;
+use mg_rs_piston::world::{BasicWorld, World};
+
+struct Chair;
+
+impl Sketch for Chair {
+    fn setup(&mut self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig, _random: &Random) {
+        ctx.background = [0.9, 0.9, 0.85, 1.0];
+        rig.look_from(cgmath::Vector3::new(0.0, 1.0, 2.0), cgmath::Vector3::new(0.0, 0.5, 0.0));
+
+        let mut chair = EntityObj::from_obj(ctx, "Eames_chair_DSW/Eames_chair_DSW.obj");
+        chair.normalize();
+        let id = world.add_entity(Box::new(chair));
+        world.set_name(id, Some("chair".to_string()));
+
+        let sun = world.create();
+        world.transforms.insert(sun, Transform::identity());
+        world.lights.insert(sun, Light::Directional {
+            direction: [-0.5, -1.0, -0.3],
+            color: [1.0, 0.95, 0.9],
+        });
+
+        let fill = world.create();
+        world.transforms.insert(fill, Transform::from_translation(cgmath::Vector3::new(1.0, 1.0, 1.0)));
+        world.lights.insert(fill, Light::Point {
+            color: [0.3, 0.4, 0.6],
+            distance: 5.0,
+            decay: 2.0,
+        });
+    }
+}
+
+fn main() {
+    let mut registry = SketchRegistry::builtin();
+    registry.register("chair", || Box::new(Chair));
+    app::run(registry);
+}
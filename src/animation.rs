@@ -0,0 +1,400 @@
+use std;
+use cgmath::{self, prelude::*};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    // holds the start value until the next keyframe
+    Step,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::Step => if t < 1.0 { 0.0 } else { 1.0 },
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => t * (2.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 { 2.0 * t * t } else { -1.0 + (4.0 - 2.0 * t) * t }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => {
+                let u = t - 1.0;
+                u * u * u + 1.0
+            }
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let u = 2.0 * t - 2.0;
+                    0.5 * u * u * u + 1.0
+                }
+            }
+            Easing::SineInOut => 0.5 * (1.0 - (std::f32::consts::PI * t).cos()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Repeat {
+    Once,
+    Loop,
+    PingPong,
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+}
+
+impl Interpolate for [f32; 3] {
+    fn interpolate(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+        [
+            f32::interpolate(a[0], b[0], t),
+            f32::interpolate(a[1], b[1], t),
+            f32::interpolate(a[2], b[2], t),
+        ]
+    }
+}
+
+impl Interpolate for cgmath::Vector3<f32> {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        a.lerp(b, t)
+    }
+}
+
+impl Interpolate for cgmath::Quaternion<f32> {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        a.slerp(b, t)
+    }
+}
+
+// `easing` shapes the segment from this keyframe to the next one
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<T: Interpolate> {
+    pub time: f32,
+    pub value: T,
+    pub easing: Easing,
+}
+
+#[derive(Clone, Debug)]
+pub struct Track<T: Interpolate> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new() -> Self {
+        Track {
+            keys: Vec::new(),
+        }
+    }
+
+    // two keyframe track from `from` to `to`
+    pub fn tween(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Track::new()
+            .key(0.0, from, easing)
+            .key(duration, to, Easing::Linear)
+    }
+
+    pub fn key(mut self, time: f32, value: T, easing: Easing) -> Self {
+        self.insert(Keyframe { time, value, easing });
+        self
+    }
+
+    pub fn insert(&mut self, key: Keyframe<T>) {
+        let index = self.keys
+            .iter()
+            .position(|k| k.time > key.time)
+            .unwrap_or(self.keys.len());
+        self.keys.insert(index, key);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+        let i = self.keys.iter().rposition(|k| k.time <= time).unwrap_or(0);
+        let a = &self.keys[i];
+        let b = &self.keys[i + 1];
+        let span = b.time - a.time;
+        let t = if span > 0.0 { (time - a.time) / span } else { 1.0 };
+        Some(T::interpolate(a.value, b.value, a.easing.apply(t)))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Channel {
+    Translation(Track<cgmath::Vector3<f32>>),
+    Rotation(Track<cgmath::Quaternion<f32>>),
+    Scale(Track<cgmath::Vector3<f32>>),
+    Albedo(Track<[f32; 3]>),
+    Emissive(Track<[f32; 3]>),
+    Roughness(Track<f32>),
+    LightColor(Track<[f32; 3]>),
+    LightDistance(Track<f32>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AnimatedValue {
+    Translation(cgmath::Vector3<f32>),
+    Rotation(cgmath::Quaternion<f32>),
+    Scale(cgmath::Vector3<f32>),
+    Albedo([f32; 3]),
+    Emissive([f32; 3]),
+    Roughness(f32),
+    LightColor([f32; 3]),
+    LightDistance(f32),
+}
+
+impl Channel {
+    pub fn duration(&self) -> f32 {
+        match *self {
+            Channel::Translation(ref t) => t.duration(),
+            Channel::Rotation(ref t) => t.duration(),
+            Channel::Scale(ref t) => t.duration(),
+            Channel::Albedo(ref t) => t.duration(),
+            Channel::Emissive(ref t) => t.duration(),
+            Channel::Roughness(ref t) => t.duration(),
+            Channel::LightColor(ref t) => t.duration(),
+            Channel::LightDistance(ref t) => t.duration(),
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> Option<AnimatedValue> {
+        match *self {
+            Channel::Translation(ref t) => t.sample(time).map(AnimatedValue::Translation),
+            Channel::Rotation(ref t) => t.sample(time).map(AnimatedValue::Rotation),
+            Channel::Scale(ref t) => t.sample(time).map(AnimatedValue::Scale),
+            Channel::Albedo(ref t) => t.sample(time).map(AnimatedValue::Albedo),
+            Channel::Emissive(ref t) => t.sample(time).map(AnimatedValue::Emissive),
+            Channel::Roughness(ref t) => t.sample(time).map(AnimatedValue::Roughness),
+            Channel::LightColor(ref t) => t.sample(time).map(AnimatedValue::LightColor),
+            Channel::LightDistance(ref t) => t.sample(time).map(AnimatedValue::LightDistance),
+        }
+    }
+}
+
+// a clip of channels sharing one clock; repeats over the longest channel
+pub struct Animation {
+    pub channels: Vec<Channel>,
+    pub repeat: Repeat,
+    pub speed: f64,
+    pub playing: bool,
+    time: f64,
+}
+
+impl Animation {
+    pub fn new(repeat: Repeat) -> Self {
+        Animation {
+            channels: Vec::new(),
+            repeat,
+            speed: 1.0,
+            playing: true,
+            time: 0.0,
+        }
+    }
+
+    pub fn with(mut self, channel: Channel) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.channels.iter().map(|c| c.duration()).fold(0.0, f32::max)
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.repeat == Repeat::Once && self.time >= self.duration() as f64
+    }
+
+    pub fn advance(&mut self, dt: f64) {
+        if self.playing {
+            self.time += dt * self.speed;
+        }
+    }
+
+    // clip time after applying the repeat mode
+    pub fn local_time(&self) -> f32 {
+        let duration = self.duration() as f64;
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let t = match self.repeat {
+            Repeat::Once => self.time.max(0.0).min(duration),
+            Repeat::Loop => wrap(self.time, duration),
+            Repeat::PingPong => {
+                let p = wrap(self.time, 2.0 * duration);
+                if p > duration { 2.0 * duration - p } else { p }
+            }
+        };
+        t as f32
+    }
+
+    pub fn sample(&self) -> Vec<AnimatedValue> {
+        let t = self.local_time();
+        self.channels.iter().filter_map(|c| c.sample(t)).collect()
+    }
+}
+
+// modulo that stays positive when playing backwards
+fn wrap(t: f64, period: f64) -> f64 {
+    ((t % period) + period) % period
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(repeat: Repeat) -> Animation {
+        Animation::new(repeat).with(Channel::Roughness(Track::tween(0.0, 1.0, 2.0, Easing::Linear)))
+    }
+
+    fn at(repeat: Repeat, time: f64) -> f32 {
+        let mut animation = clip(repeat);
+        animation.seek(time);
+        animation.local_time()
+    }
+
+    #[test]
+    fn once_clamps_to_the_clip() {
+        assert_eq!(at(Repeat::Once, 0.0), 0.0);
+        assert_eq!(at(Repeat::Once, 0.5), 0.5);
+        assert_eq!(at(Repeat::Once, 2.0), 2.0);
+        assert_eq!(at(Repeat::Once, 5.0), 2.0);
+        assert_eq!(at(Repeat::Once, -1.0), 0.0);
+
+        let mut animation = clip(Repeat::Once);
+        animation.advance(1.5);
+        assert!(!animation.is_finished());
+        animation.advance(0.5);
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn loop_wraps_in_both_directions() {
+        assert_eq!(at(Repeat::Loop, 0.0), 0.0);
+        assert_eq!(at(Repeat::Loop, 2.0), 0.0);
+        assert_eq!(at(Repeat::Loop, 3.0), 1.0);
+        assert_eq!(at(Repeat::Loop, -0.5), 1.5);
+
+        let mut animation = clip(Repeat::Loop);
+        animation.advance(-4.5);
+        assert_eq!(animation.local_time(), 1.5);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_the_ends() {
+        assert_eq!(at(Repeat::PingPong, 0.0), 0.0);
+        assert_eq!(at(Repeat::PingPong, 2.0), 2.0);
+        assert_eq!(at(Repeat::PingPong, 3.0), 1.0);
+        assert_eq!(at(Repeat::PingPong, 4.0), 0.0);
+        assert_eq!(at(Repeat::PingPong, -0.5), 0.5);
+    }
+
+    #[test]
+    fn speed_and_pause_scale_the_clock() {
+        let mut animation = clip(Repeat::Loop);
+        animation.speed = -2.0;
+        animation.advance(0.25);
+        assert_eq!(animation.time(), -0.5);
+        assert_eq!(animation.local_time(), 1.5);
+
+        animation.playing = false;
+        animation.advance(1.0);
+        assert_eq!(animation.time(), -0.5);
+    }
+
+    #[test]
+    fn zero_length_clips_stay_at_the_start() {
+        for &repeat in &[Repeat::Once, Repeat::Loop, Repeat::PingPong] {
+            let mut animation = Animation::new(repeat)
+                .with(Channel::Roughness(Track::tween(0.25, 0.75, 0.0, Easing::Linear)));
+            assert_eq!(animation.duration(), 0.0);
+            for &time in &[0.0, 1.0, -1.0] {
+                animation.seek(time);
+                assert_eq!(animation.local_time(), 0.0);
+                match animation.sample()[..] {
+                    [AnimatedValue::Roughness(value)] => assert_eq!(value, 0.25),
+                    ref other => panic!("unexpected sample {:?}", other),
+                }
+            }
+
+            let empty = Animation::new(repeat).with(Channel::Roughness(Track::new()));
+            assert_eq!(empty.local_time(), 0.0);
+            assert!(empty.sample().is_empty());
+        }
+    }
+
+    #[test]
+    fn tracks_hold_their_ends_and_interpolate_between_keys() {
+        let track = Track::<f32>::new()
+            .key(2.0, 4.0, Easing::Linear)
+            .key(0.0, 0.0, Easing::Linear)
+            .key(1.0, 2.0, Easing::Step);
+        assert_eq!(track.duration(), 2.0);
+        assert_eq!(track.sample(-1.0), Some(0.0));
+        assert_eq!(track.sample(0.0), Some(0.0));
+        assert_eq!(track.sample(0.5), Some(1.0));
+        // the step segment holds its start value until the next key
+        assert_eq!(track.sample(1.0), Some(2.0));
+        assert_eq!(track.sample(1.99), Some(2.0));
+        assert_eq!(track.sample(2.0), Some(4.0));
+        assert_eq!(track.sample(3.0), Some(4.0));
+
+        assert_eq!(Track::<f32>::new().sample(0.0), None);
+        assert_eq!(Track::<f32>::new().key(1.0, 3.0, Easing::Linear).sample(0.0), Some(3.0));
+    }
+
+    #[test]
+    fn easings_are_clamped_and_pinned_at_the_ends() {
+        let all = [
+            Easing::Linear,
+            Easing::Step,
+            Easing::QuadIn,
+            Easing::QuadOut,
+            Easing::QuadInOut,
+            Easing::CubicIn,
+            Easing::CubicOut,
+            Easing::CubicInOut,
+            Easing::SineInOut,
+        ];
+        for easing in &all {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", easing);
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0));
+            assert_eq!(easing.apply(2.0), easing.apply(1.0));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use piston_window::*;
+use camera::{CameraMode, CameraRig};
+use clock::{self, SimClock};
+use export::Recipe;
+use random::Random;
+use camera_path::{CameraPath, CameraPathPlayer, Interpolation};
+use scene::{CameraDesc, EntityKind, SceneDesc};
+use sketch::SketchRegistry;
+use sketch::scene_sketch::SceneSketch;
+use world::{BasicWorld, World};
+use cgmath;
+use ui_handler;
+use render;
+use params;
+use batch;
+use export;
+use options::{self, Options};
+use std;
+use std::path::Path;
+use std::fs::File;
+use std::collections::BTreeMap;
+
+const CAMERA_PATH_FILE: &str = "camera_path.txt";
+const SCENE_SAVE_FILE: &str = "scene.ron";
+
+// parses the command line and runs the viewer on top of `registry`
+pub fn run(registry: SketchRegistry) {
+    match Options::from_env() {
+        Ok(Some(options)) => run_with(registry, options),
+        Ok(None) => println!("{}", options::USAGE),
+        Err(err) => eprintln!("{}\n\n{}", err, options::USAGE),
+    }
+}
+
+// scene files and exported images named in `options` are added to the registry
+pub fn run_with(mut registry: SketchRegistry, options: Options) {
+    // headless runs still need a window for the GL context, it is hidden right after creation
+    let mut window: PistonWindow =
+        WindowSettings::new("mg-rs", [options.width, options.height])
+            .exit_on_esc(true)
+            .fullscreen(options.fullscreen)
+            .vsync(options.vsync)
+            .samples(options.samples)
+            .opengl(OpenGL::V3_2)
+            .build()
+            .unwrap();
+    if options.headless {
+        window.window.window.hide();
+    }
+    let mut capture = !options.headless;
+    window.set_capture_cursor(capture);
+
+    let mut ui = ui_handler::UIHandler::new(&mut window, options.width, options.height);
+
+    let mut ctx = render::RenderContext::new(&window);
+    let mut rig = CameraRig::new([0.5, 0.5, 1.0]);
+    let mut selected = None;
+    let mut path = CameraPath::new(Interpolation::CatmullRom);
+    let mut player = CameraPathPlayer::new();
+    let mut sim_clock = SimClock::new();
+
+    let scene_path = options.scene.clone();
+    if let Some(ref batch_path) = options.batch {
+        let mut desc = match batch::BatchDesc::load(batch_path) {
+            Ok(desc) => desc,
+            Err(err) => {
+                eprintln!("failed to load batch {}: {}", batch_path, err);
+                return;
+            }
+        };
+        if let Some(ref output) = options.output {
+            desc.output = output.clone();
+        }
+        if let Err(err) = batch::run(&desc, &mut window, &mut ctx, &mut rig) {
+            eprintln!("batch failed: {}", err);
+        }
+        return;
+    }
+
+    // an exported png restores the session it was rendered from
+    let recipe = match scene_path {
+        Some(ref path) if path.ends_with(".png") => Some(Recipe::load_png(path).unwrap()),
+        _ => None,
+    };
+    let mut scene_seed = None;
+    let mut sketch_index = match (&recipe, &scene_path) {
+        (&Some(ref recipe), &Some(ref path)) => {
+            scene_seed = Some(recipe.seed);
+            match recipe.sketch.as_ref().and_then(|name| registry.index_of(name)) {
+                Some(index) => index,
+                None => {
+                    let scene = recipe.scene.clone();
+                    registry.register(&sketch_file_name(path), move || Box::new(SceneSketch::new(scene.clone())))
+                }
+            }
+        }
+        (_, &Some(ref path)) => {
+            let scene = SceneDesc::load(path).unwrap();
+            scene_seed = scene.seed;
+            registry.register(&sketch_file_name(path), move || Box::new(SceneSketch::new(scene.clone())))
+        }
+        _ => options.sketch.as_ref().and_then(|name| registry.index_of(name)).unwrap_or(0),
+    };
+    ui.set_sketches(registry.names(), sketch_index);
+
+    let mut random = options.seed.or(scene_seed).map_or_else(Random::from_entropy, Random::new);
+    println!("seed: {}", random.seed());
+    ui.set_seed(random.seed());
+    let mut world = BasicWorld::new();
+    let mut sketch = registry.create(sketch_index);
+    sketch.setup(&mut world, &mut ctx, &mut rig, &random);
+    add_objs(&mut world, &mut ctx, &options.objs);
+    // values applied through `params::apply`, embedded in exports
+    let mut applied = BTreeMap::new();
+    if let Some(ref recipe) = recipe {
+        // a registered sketch only rebuilds its own defaults, put the captured state back
+        recipe.scene.restore(&mut world, &mut ctx, &mut rig);
+        applied = apply_params(&mut world, &mut ctx, &recipe.params);
+        // replayed in lockstep so floating entities come out the same every time
+        world.lockstep = true;
+        let steps = (recipe.time / clock::STEP_DT).round() as u64;
+        for _ in 0..steps {
+            world.update(clock::STEP_DT);
+            sketch.update(&mut world, clock::STEP_DT);
+        }
+        world.lockstep = false;
+        sim_clock.set_time(recipe.time);
+    }
+    if options.headless {
+        world.lockstep = true;
+        for _ in 0..options.frames {
+            world.update(clock::STEP_DT);
+            sketch.update(&mut world, clock::STEP_DT);
+        }
+        let time = sim_clock.time() + options.frames as f64 * clock::STEP_DT;
+        sim_clock.set_time(time);
+        let file = options.output.clone().unwrap_or_else(|| format!("export_seed{}.png", random.seed()));
+        let target = render::Offscreen::new(&mut ctx.factory, options.width, options.height);
+        world.sync();
+        ctx.set_view(rig.view(0.0));
+        let pixels = ctx.render_offscreen(&mut world, &target, &mut window);
+        let recipe = Recipe {
+            scene_file: scene_path.clone(),
+            sketch: Some(registry.name(sketch_index).to_string()),
+            scene: SceneDesc::capture(&world, &ctx, &mut rig, &random),
+            seed: random.seed(),
+            time: sim_clock.time(),
+            params: applied.clone(),
+        };
+        match export::save_png(&file, options.width, options.height, &pixels, &recipe) {
+            Ok(()) => println!("exported {}", file),
+            Err(err) => eprintln!("failed to export image: {}", err),
+        }
+        return;
+    }
+
+    let mut exported = 0;
+    let default_fov = ctx.fov;
+    if rig.mode == CameraMode::Orbit {
+        capture = false;
+        window.set_capture_cursor(capture);
+    }
+
+    let mut cursor = [0.0, 0.0];
+    let mut disturbing = false;
+
+    while let Some(e) = window.next() {
+        if capture || rig.mode == CameraMode::Orbit {
+            rig.event(&e);
+        }
+        // right button drops into the fluid under the cursor and drags while held;
+        // with a captured cursor the ray goes through the centre of the view
+        e.mouse_cursor(|x, y| cursor = [x, y]);
+        // one kind of motion event per mode, a drag would otherwise disturb twice per move
+        let moved = if capture { e.mouse_relative_args().is_some() } else { e.mouse_cursor_args().is_some() };
+        let disturbance = match e.press_args() {
+            Some(Mouse(MouseButton::Right)) => {
+                disturbing = true;
+                Some(options.drop)
+            }
+            _ if disturbing && moved => Some(options.drag),
+            _ => None,
+        };
+        if let Some(Mouse(MouseButton::Right)) = e.release_args() {
+            disturbing = false;
+        }
+        if let Some(disturbance) = disturbance {
+            let size = window.size();
+            let (x, y) = if capture { (size.width as f64 / 2.0, size.height as f64 / 2.0) } else { (cursor[0], cursor[1]) };
+            if let Some(ray) = ctx.screen_ray(x, y, size.width as f64, size.height as f64) {
+                if let Some((id, t)) = world.raycast(&ray) {
+                    world.disturb(id, ray.at(t), &disturbance);
+                }
+            }
+        }
+        // the selected entity's fluid, or the first one in the scene
+        let fluid_target = selected
+            .filter(|&id| world.fluid_params(id).is_some())
+            .or_else(|| world.find_fluid());
+        let mut fluid = fluid_target.and_then(|id| world.fluid_params(id));
+        if ui.update(&mut window, &e, &mut sim_clock, fluid.as_mut()) {
+            if let (Some(id), Some(params)) = (fluid_target, fluid) {
+                world.set_fluid_params(id, params);
+            }
+        }
+        sketch.on_input(&mut world, &e);
+        if let Some(seed) = ui.seed_request.take() {
+            random = Random::new(seed);
+            println!("seed: {}", random.seed());
+            ui.set_seed(random.seed());
+            // keep looking from the same place while flipping through seeds
+            let camera = CameraDesc::capture(&ctx, &mut rig);
+            world = BasicWorld::new();
+            sketch.setup(&mut world, &mut ctx, &mut rig, &random);
+            add_objs(&mut world, &mut ctx, &options.objs);
+            applied = apply_params(&mut world, &mut ctx, &applied);
+            camera.apply(&mut ctx, &mut rig);
+        }
+        if let Some(index) = ui.sketch_request.take() {
+            sketch_index = index;
+            println!("sketch: {}", registry.name(sketch_index));
+            ui.set_sketch(sketch_index);
+            world = BasicWorld::new();
+            sketch = registry.create(sketch_index);
+            sketch.setup(&mut world, &mut ctx, &mut rig, &random);
+            add_objs(&mut world, &mut ctx, &options.objs);
+            // the values were for the previous sketch's entities
+            applied.clear();
+            selected = None;
+        }
+        use piston_window::Event::*;
+        use piston_window::Loop::*;
+        use piston_window::Input::*;
+        use piston_window::Key;
+        use piston_window::Button::*;
+        match e {
+            Loop(Render(RenderArgs { ext_dt, .. })) => {
+                match player.sample(&path) {
+                    Some(ref key) if player.playing => {
+                        ctx.set_fov(key.fov);
+                        ctx.set_view(cgmath::Matrix4::from(key.camera().orthogonal()));
+                    }
+                    _ => ctx.set_view(rig.view(ext_dt)),
+                }
+                window.draw_3d(&e, |window| {
+                    ctx.draw_world(&mut world, window, ext_dt);
+                });
+                ui.status = format!("drawn: {} culled: {} {}", ctx.stats.drawn, ctx.stats.culled, sim_clock.status());
+            }
+            Loop(Update(UpdateArgs { dt })) => {
+                if player.advance(dt, &path) {
+                    ctx.set_fov(default_fov);
+                }
+                if let Some(sim_dt) = sim_clock.tick(dt) {
+                    world.update(sim_dt);
+                    sketch.update(&mut world, sim_dt);
+                }
+            }
+            Input(Resize(x, y)) => {
+                ctx.resize(&window, x, y);
+            }
+            Input(Button(ButtonArgs {
+                             state,
+                             button,
+                             ..
+                         })) => {
+                match button {
+                    Keyboard(key) => {
+                        match key {
+                            Key::E => {
+                                if state == ButtonState::Release {
+                                    capture = !capture;
+                                    window.set_capture_cursor(capture);
+                                }
+                            }
+                            Key::C => {
+                                if state == ButtonState::Release {
+                                    rig.toggle();
+                                    capture = rig.mode == CameraMode::Fly;
+                                    window.set_capture_cursor(capture);
+                                }
+                            }
+                            Key::Tab => {
+                                if state == ButtonState::Release {
+                                    let ids = world.ids();
+                                    let next = selected
+                                        .and_then(|id| ids.iter().position(|&x| x == id))
+                                        .map_or(0, |i| i + 1);
+                                    selected = ids.get(next).or_else(|| ids.first()).cloned();
+                                }
+                            }
+                            Key::F => {
+                                if state == ButtonState::Release {
+                                    if let Some(bounds) = selected.and_then(|id| world.bounds(id)) {
+                                        rig.frame(&bounds, ctx.fov);
+                                        capture = false;
+                                        window.set_capture_cursor(capture);
+                                    }
+                                }
+                            }
+                            Key::K => {
+                                if state == ButtonState::Release {
+                                    path.capture(&rig.camera(0.0), ctx.fov);
+                                }
+                            }
+                            Key::P => {
+                                if state == ButtonState::Release {
+                                    if player.playing {
+                                        player.stop();
+                                        ctx.set_fov(default_fov);
+                                    } else {
+                                        player.play();
+                                    }
+                                }
+                            }
+                            Key::J => {
+                                if state == ButtonState::Release {
+                                    path.looping = !path.looping;
+                                    println!("camera path looping: {}", path.looping);
+                                }
+                            }
+                            Key::O => {
+                                if state == ButtonState::Release {
+                                    if let Err(err) = path.save(CAMERA_PATH_FILE) {
+                                        eprintln!("failed to save camera path: {}", err);
+                                    }
+                                }
+                            }
+                            Key::L => {
+                                if state == ButtonState::Release {
+                                    match CameraPath::load(CAMERA_PATH_FILE) {
+                                        Ok(loaded) => path = loaded,
+                                        Err(err) => eprintln!("failed to load camera path: {}", err),
+                                    }
+                                }
+                            }
+                            Key::F5 => {
+                                if state == ButtonState::Release {
+                                    let desc = SceneDesc::capture(&world, &ctx, &mut rig, &random);
+                                    if let Err(err) = desc.save(SCENE_SAVE_FILE) {
+                                        eprintln!("failed to save scene: {}", err);
+                                    }
+                                }
+                            }
+                            Key::F12 => {
+                                if state == ButtonState::Release {
+                                    let size = window.draw_size();
+                                    let target = render::Offscreen::new(&mut ctx.factory, size.width, size.height);
+                                    let pixels = ctx.render_offscreen(&mut world, &target, &mut window);
+                                    let recipe = Recipe {
+                                        scene_file: scene_path.clone(),
+                                        sketch: Some(registry.name(sketch_index).to_string()),
+                                        scene: SceneDesc::capture(&world, &ctx, &mut rig, &random),
+                                        seed: random.seed(),
+                                        time: sim_clock.time(),
+                                        params: applied.clone(),
+                                    };
+                                    let file = format!("export_seed{}_{:03}.png", random.seed(), exported);
+                                    match export::save_png(&file, size.width, size.height, &pixels, &recipe) {
+                                        Ok(()) => {
+                                            println!("exported {}", file);
+                                            exported += 1;
+                                        }
+                                        Err(err) => eprintln!("failed to export image: {}", err),
+                                    }
+                                }
+                            }
+                            Key::N => {
+                                if state == ButtonState::Release {
+                                    ui.sketch_request = Some((sketch_index + 1) % registry.len());
+                                }
+                            }
+                            Key::B => {
+                                if state == ButtonState::Release {
+                                    ui.sketch_request = Some((sketch_index + registry.len() - 1) % registry.len());
+                                }
+                            }
+                            Key::T => {
+                                if state == ButtonState::Release {
+                                    sim_clock.toggle_pause();
+                                }
+                            }
+                            Key::Period => {
+                                if state == ButtonState::Release {
+                                    sim_clock.step();
+                                }
+                            }
+                            Key::LeftBracket => {
+                                if state == ButtonState::Release {
+                                    sim_clock.slower();
+                                }
+                            }
+                            Key::RightBracket => {
+                                if state == ButtonState::Release {
+                                    sim_clock.faster();
+                                }
+                            }
+                            Key::R => {
+                                if state == ButtonState::Release {
+                                    random.reroll();
+                                    ui.seed_request = Some(random.seed());
+                                }
+                            }
+                            Key::Backspace => {
+                                if state == ButtonState::Release {
+                                    path.clear();
+                                }
+                            }
+                            _ => ()
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// returns the values that applied; the others are reported and dropped
+fn apply_params(world: &mut BasicWorld, ctx: &mut render::RenderContext, values: &BTreeMap<String, f32>) -> BTreeMap<String, f32> {
+    let mut applied = BTreeMap::new();
+    for (key, &value) in values {
+        match params::apply(world, ctx, key, value) {
+            Ok(()) => {
+                applied.insert(key.clone(), value);
+            }
+            Err(err) => eprintln!("failed to apply parameter: {}", err),
+        }
+    }
+    applied
+}
+
+// paths are taken relative to the working directory when they exist there, otherwise to assets/models
+fn add_objs(world: &mut BasicWorld, ctx: &mut render::RenderContext, objs: &[String]) {
+    for obj in objs {
+        let path = std::fs::canonicalize(obj)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| obj.clone());
+        // models from the command line come in any units, so they're scaled to fit
+        let id = EntityKind::Obj { path, normalize: true }.create(world, ctx);
+        world.set_name(id, Some(sketch_file_name(obj)));
+    }
+}
+
+// scene files given on the command line are registered under their file name
+fn sketch_file_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or_else(|| path.to_string(), |stem| stem.to_string_lossy().into_owned())
+}
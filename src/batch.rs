@@ -0,0 +1,259 @@
+extern crate image;
+
+use std;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use piston_window::PistonWindow;
+use ron;
+use serde_json;
+use camera::CameraRig;
+use export::{self, Recipe};
+use params;
+use random::Random;
+use render::{Offscreen, RenderContext};
+use scene::SceneDesc;
+use world::World;
+
+// one image per seed and grid point, e.g.
+// (scene: "assets/scenes/default.ron", seed_range: Some((0, 10)),
+//  grid: [(param: "water.roughness", values: [0.1, 0.5, 0.9])])
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatchDesc {
+    pub scene: String,
+    #[serde(default)]
+    pub seeds: Vec<u64>,
+    // (first seed, count)
+    #[serde(default)]
+    pub seed_range: Option<(u64, u64)>,
+    #[serde(default)]
+    pub grid: Vec<GridAxis>,
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+    #[serde(default = "default_dt")]
+    pub dt: f64,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_output")]
+    pub output: String,
+    #[serde(default = "default_columns")]
+    pub columns: u32,
+    #[serde(default = "default_thumbnail_width")]
+    pub thumbnail_width: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GridAxis {
+    pub param: String,
+    pub values: Vec<f32>,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    scene: String,
+    frames: u32,
+    dt: f64,
+    width: u32,
+    height: u32,
+    contact_sheet: String,
+    items: Vec<ManifestItem>,
+}
+
+#[derive(Serialize)]
+struct ManifestItem {
+    file: String,
+    seed: u64,
+    params: BTreeMap<String, f32>,
+}
+
+impl BatchDesc {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        ron::de::from_reader(file).map_err(|e| invalid_data(e.to_string()))
+    }
+
+    // falls back to the scene's own seed when none are listed
+    fn seeds(&self, scene_seed: Option<u64>) -> Vec<u64> {
+        let mut seeds = self.seeds.clone();
+        if let Some((first, count)) = self.seed_range {
+            seeds.extend(first..first + count);
+        }
+        if seeds.is_empty() {
+            seeds.push(scene_seed.unwrap_or(0));
+        }
+        seeds
+    }
+
+    // cartesian product of the grid axes
+    fn combinations(&self) -> Vec<BTreeMap<String, f32>> {
+        let mut combinations = vec![BTreeMap::new()];
+        for axis in &self.grid {
+            combinations = combinations
+                .iter()
+                .flat_map(|params| {
+                    axis.values.iter().map(move |&value| {
+                        let mut params = params.clone();
+                        params.insert(axis.param.clone(), value);
+                        params
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+}
+
+pub fn run(desc: &BatchDesc, window: &mut PistonWindow, ctx: &mut RenderContext, rig: &mut CameraRig) -> io::Result<()> {
+    let scene = SceneDesc::load(&desc.scene)?;
+    let output = Path::new(&desc.output);
+    std::fs::create_dir_all(output)?;
+
+    let target = Offscreen::new(&mut ctx.factory, desc.width, desc.height);
+    let thumbnail_height = desc.thumbnail_width * desc.height / desc.width;
+    let mut thumbnails = Vec::new();
+    let mut items = Vec::new();
+
+    let combinations = desc.combinations();
+    let seeds = desc.seeds(scene.seed);
+    let total = seeds.len() * combinations.len();
+    for seed in seeds {
+        for values in &combinations {
+            let random = Random::new(seed);
+            let mut world = scene.build(ctx, rig, &random);
+            world.lockstep = true;
+            for (key, &value) in values {
+                params::apply(&mut world, ctx, key, value)?;
+            }
+            for _ in 0..desc.frames {
+                world.update(desc.dt);
+            }
+            world.sync();
+            ctx.set_view(rig.view(0.0));
+            let pixels = ctx.render_offscreen(&mut world, &target, window);
+
+            let file = format!("{:04}_seed{}.png", items.len(), seed);
+            let recipe = Recipe {
+                scene_file: Some(desc.scene.clone()),
+                sketch: None,
+                scene: SceneDesc { seed: Some(seed), ..scene.clone() },
+                seed,
+                time: desc.frames as f64 * desc.dt,
+                params: values.clone(),
+            };
+            export::save_png(output.join(&file), desc.width, desc.height, &pixels, &recipe)?;
+            println!("{} ({} of {})", file, items.len() + 1, total);
+
+            let img = image::RgbaImage::from_raw(desc.width, desc.height, pixels)
+                .ok_or_else(|| invalid_data("unexpected image size".to_string()))?;
+            thumbnails.push(image::imageops::thumbnail(&img, desc.thumbnail_width, thumbnail_height));
+            items.push(ManifestItem {
+                file,
+                seed,
+                params: values.clone(),
+            });
+        }
+    }
+
+    let columns = desc.columns.max(1);
+    let rows = (thumbnails.len() as u32 + columns - 1) / columns;
+    let mut sheet = image::RgbaImage::new(columns * desc.thumbnail_width, rows.max(1) * thumbnail_height);
+    for (i, thumbnail) in thumbnails.iter().enumerate() {
+        let i = i as u32;
+        let x = (i % columns) * desc.thumbnail_width;
+        let y = (i / columns) * thumbnail_height;
+        image::imageops::overlay(&mut sheet, thumbnail, x, y);
+    }
+    let contact_sheet = "contact_sheet.png".to_string();
+    sheet.save(output.join(&contact_sheet))?;
+
+    let manifest = Manifest {
+        scene: desc.scene.clone(),
+        frames: desc.frames,
+        dt: desc.dt,
+        width: desc.width,
+        height: desc.height,
+        contact_sheet,
+        items,
+    };
+    let text = serde_json::to_string_pretty(&manifest).map_err(|e| invalid_data(e.to_string()))?;
+    std::fs::write(output.join("manifest.json"), text)
+}
+
+fn default_frames() -> u32 {
+    120
+}
+
+fn default_dt() -> f64 {
+    1.0 / 60.0
+}
+
+fn default_width() -> u32 {
+    1024
+}
+
+fn default_height() -> u32 {
+    768
+}
+
+fn default_output() -> String {
+    "batch".to_string()
+}
+
+fn default_columns() -> u32 {
+    10
+}
+
+fn default_thumbnail_width() -> u32 {
+    256
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(text: &str) -> BatchDesc {
+        ron::de::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn no_grid_is_a_single_empty_combination() {
+        let combinations = desc("(scene: \"a.ron\")").combinations();
+        assert_eq!(combinations.len(), 1);
+        assert!(combinations[0].is_empty());
+    }
+
+    #[test]
+    fn combinations_cover_every_grid_point() {
+        let desc = desc("(scene: \"a.ron\", grid: [
+            (param: \"water.roughness\", values: [0.1, 0.5, 0.9]),
+            (param: \"camera.fov\", values: [45.0, 90.0]),
+        ])");
+        let combinations = desc.combinations();
+        assert_eq!(combinations.len(), 6);
+        for &roughness in &[0.1, 0.5, 0.9] {
+            for &fov in &[45.0, 90.0] {
+                assert!(combinations.iter().any(|c| c["water.roughness"] == roughness && c["camera.fov"] == fov));
+            }
+        }
+        assert!(combinations.iter().all(|c| c.len() == 2));
+    }
+
+    #[test]
+    fn an_empty_axis_leaves_nothing_to_render() {
+        let desc = desc("(scene: \"a.ron\", grid: [(param: \"water.roughness\", values: [])])");
+        assert!(desc.combinations().is_empty());
+    }
+
+    #[test]
+    fn seeds_combine_list_and_range_or_fall_back() {
+        assert_eq!(desc("(scene: \"a.ron\", seeds: [7], seed_range: Some((2, 3)))").seeds(Some(1)), vec![7, 2, 3, 4]);
+        assert_eq!(desc("(scene: \"a.ron\")").seeds(Some(5)), vec![5]);
+        assert_eq!(desc("(scene: \"a.ron\")").seeds(None), vec![0]);
+    }
+}
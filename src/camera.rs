@@ -0,0 +1,240 @@
+use std;
+use cgmath::{self, prelude::*};
+use camera_controllers::{Camera, FirstPerson, FirstPersonSettings};
+use piston_window::{
+    Button,
+    GenericEvent,
+    MouseButton,
+    MouseRelativeEvent,
+    MouseScrollEvent,
+    PressEvent,
+    ReleaseEvent,
+};
+use mesh;
+
+pub struct OrbitCameraSettings {
+    pub rotate_button: Button,
+    pub pan_button: Button,
+    pub rotate_speed: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl Default for OrbitCameraSettings {
+    fn default() -> Self {
+        OrbitCameraSettings {
+            rotate_button: Button::Mouse(MouseButton::Left),
+            pan_button: Button::Mouse(MouseButton::Middle),
+            rotate_speed: 0.005,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: 500.0,
+        }
+    }
+}
+
+// yaw/pitch follow the camera_controllers convention, so the camera sits at
+// target + distance * (sin(yaw)cos(pitch), sin(pitch), cos(yaw)cos(pitch))
+pub struct OrbitCamera {
+    pub target: cgmath::Vector3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub settings: OrbitCameraSettings,
+    rotating: bool,
+    panning: bool,
+}
+
+impl OrbitCamera {
+    pub fn new(target: cgmath::Vector3<f32>, distance: f32, settings: OrbitCameraSettings) -> Self {
+        OrbitCamera {
+            target,
+            yaw: 0.0,
+            pitch: 0.3,
+            distance,
+            settings,
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    pub fn direction(&self) -> cgmath::Vector3<f32> {
+        let (y_s, y_c) = self.yaw.sin_cos();
+        let (p_s, p_c) = self.pitch.sin_cos();
+        cgmath::Vector3::new(y_s * p_c, p_s, y_c * p_c)
+    }
+
+    pub fn position(&self) -> cgmath::Vector3<f32> {
+        self.target + self.direction() * self.distance
+    }
+
+    pub fn camera(&self) -> Camera<f32> {
+        let mut camera = Camera::new(self.position().into());
+        camera.look_at(self.target.into());
+        camera
+    }
+
+    // fit a bounding box into a vertical field of view given in degrees
+    pub fn frame(&mut self, bounds: &mesh::Aabb, fov: f32) {
+        let half = (fov.to_radians() * 0.5).sin();
+        self.target = bounds.center();
+        self.distance = (bounds.radius() / half)
+            .max(self.settings.min_distance)
+            .min(self.settings.max_distance);
+    }
+
+    pub fn event<E: GenericEvent>(&mut self, e: &E) {
+        if let Some(button) = e.press_args() {
+            if button == self.settings.rotate_button {
+                self.rotating = true;
+            }
+            if button == self.settings.pan_button {
+                self.panning = true;
+            }
+        }
+        if let Some(button) = e.release_args() {
+            if button == self.settings.rotate_button {
+                self.rotating = false;
+            }
+            if button == self.settings.pan_button {
+                self.panning = false;
+            }
+        }
+        e.mouse_relative(|dx, dy| {
+            let (dx, dy) = (dx as f32, dy as f32);
+            if self.rotating {
+                let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+                self.yaw -= dx * self.settings.rotate_speed;
+                self.pitch = (self.pitch + dy * self.settings.rotate_speed).max(-limit).min(limit);
+            } else if self.panning {
+                let camera = self.camera();
+                let right = cgmath::Vector3::from(camera.right);
+                let up = cgmath::Vector3::from(camera.up);
+                let scale = self.settings.pan_speed * self.distance;
+                self.target += (-right * dx + up * dy) * scale;
+            }
+        });
+        e.mouse_scroll(|_, dy| {
+            let factor = 1.0 - dy as f32 * self.settings.zoom_speed;
+            self.distance = (self.distance * factor)
+                .max(self.settings.min_distance)
+                .min(self.settings.max_distance);
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CameraMode {
+    Fly,
+    Orbit,
+}
+
+pub struct CameraRig {
+    pub mode: CameraMode,
+    pub first_person: FirstPerson,
+    pub orbit: OrbitCamera,
+}
+
+impl CameraRig {
+    pub fn new(position: [f32; 3]) -> Self {
+        let mut first_person = FirstPerson::new(
+            position,
+            FirstPersonSettings::keyboard_wasd(),
+        );
+        first_person.velocity = 2.0;
+        let orbit = OrbitCamera::new(
+            cgmath::Vector3::new(0.0, 0.0, 0.0),
+            2.0,
+            OrbitCameraSettings::default(),
+        );
+        CameraRig {
+            mode: CameraMode::Fly,
+            first_person,
+            orbit,
+        }
+    }
+
+    pub fn event<E: GenericEvent>(&mut self, e: &E) {
+        match self.mode {
+            CameraMode::Fly => self.first_person.event(e),
+            CameraMode::Orbit => self.orbit.event(e),
+        }
+    }
+
+    pub fn camera(&mut self, dt: f64) -> Camera<f32> {
+        match self.mode {
+            CameraMode::Fly => self.first_person.camera(dt),
+            CameraMode::Orbit => self.orbit.camera(),
+        }
+    }
+
+    pub fn view(&mut self, dt: f64) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from(self.camera(dt).orthogonal())
+    }
+
+    // hand over position and orientation so switching does not jump the view
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            CameraMode::Orbit => {
+                let fp = &self.first_person;
+                let (y_s, y_c) = fp.yaw.sin_cos();
+                let (p_s, p_c) = fp.pitch.sin_cos();
+                let forward = cgmath::Vector3::new(y_s * p_c, p_s, y_c * p_c);
+                self.orbit.yaw = fp.yaw;
+                self.orbit.pitch = fp.pitch;
+                self.orbit.target = cgmath::Vector3::from(fp.position) - forward * self.orbit.distance;
+            }
+            CameraMode::Fly => {
+                self.first_person.position = self.orbit.position().into();
+                self.first_person.yaw = self.orbit.yaw;
+                self.first_person.pitch = self.orbit.pitch;
+            }
+        }
+        self.mode = mode;
+    }
+
+    pub fn toggle(&mut self) {
+        let mode = match self.mode {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Fly,
+        };
+        self.set_mode(mode);
+    }
+
+    // point both controllers from `position` towards `target`
+    pub fn look_from(&mut self, position: cgmath::Vector3<f32>, target: cgmath::Vector3<f32>) {
+        let offset = position - target;
+        let distance = offset.magnitude();
+        if distance > 0.0 {
+            let dir = offset / distance;
+            self.orbit.yaw = dir.x.atan2(dir.z);
+            self.orbit.pitch = dir.y.asin();
+            self.orbit.distance = distance;
+        }
+        self.orbit.target = target;
+        self.first_person.position = position.into();
+        self.first_person.yaw = self.orbit.yaw;
+        self.first_person.pitch = self.orbit.pitch;
+    }
+
+    pub fn target(&mut self) -> cgmath::Vector3<f32> {
+        match self.mode {
+            CameraMode::Fly => {
+                let camera = self.first_person.camera(0.0);
+                cgmath::Vector3::from(camera.position) - cgmath::Vector3::from(camera.forward)
+            }
+            CameraMode::Orbit => self.orbit.target,
+        }
+    }
+
+    pub fn frame(&mut self, bounds: &mesh::Aabb, fov: f32) {
+        self.set_mode(CameraMode::Orbit);
+        self.orbit.frame(bounds, fov);
+    }
+}
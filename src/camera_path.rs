@@ -0,0 +1,444 @@
+use std;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use cgmath::{self, prelude::*};
+use camera_controllers::Camera;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: cgmath::Vector3<f32>,
+    pub target: cgmath::Vector3<f32>,
+    pub fov: f32,
+}
+
+impl CameraKeyframe {
+    // camera_controllers' `forward` points from the target back to the eye
+    pub fn from_camera(time: f32, camera: &Camera<f32>, fov: f32) -> Self {
+        let position = cgmath::Vector3::from(camera.position);
+        CameraKeyframe {
+            time,
+            position,
+            target: position - cgmath::Vector3::from(camera.forward),
+            fov,
+        }
+    }
+
+    pub fn camera(&self) -> Camera<f32> {
+        let mut camera = Camera::new(self.position.into());
+        camera.look_at(self.target.into());
+        camera
+    }
+
+    fn lerp(&self, other: &CameraKeyframe, t: f32) -> Self {
+        CameraKeyframe {
+            time: self.time + (other.time - self.time) * t,
+            position: self.position.lerp(other.position, t),
+            target: self.target.lerp(other.target, t),
+            fov: self.fov + (other.fov - self.fov) * t,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    // passes through every keyframe
+    CatmullRom,
+    // treats the keyframes as control points of a single curve
+    Bezier,
+}
+
+impl Interpolation {
+    fn name(&self) -> &'static str {
+        match *self {
+            Interpolation::CatmullRom => "catmull-rom",
+            Interpolation::Bezier => "bezier",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "catmull-rom" => Some(Interpolation::CatmullRom),
+            "bezier" => Some(Interpolation::Bezier),
+            _ => None,
+        }
+    }
+}
+
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    pub interpolation: Interpolation,
+    pub spacing: f32,
+    // restart from the first keyframe instead of stopping at the last
+    pub looping: bool,
+}
+
+impl CameraPath {
+    pub fn new(interpolation: Interpolation) -> Self {
+        CameraPath {
+            keyframes: Vec::new(),
+            interpolation,
+            spacing: 2.0,
+            looping: false,
+        }
+    }
+
+    pub fn push(&mut self, keyframe: CameraKeyframe) {
+        let index = self.keyframes
+            .iter()
+            .position(|k| k.time > keyframe.time)
+            .unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, keyframe);
+    }
+
+    // append a keyframe `spacing` seconds after the last one
+    pub fn capture(&mut self, camera: &Camera<f32>, fov: f32) {
+        let time = self.keyframes.last().map_or(0.0, |k| k.time + self.spacing);
+        self.push(CameraKeyframe::from_camera(time, camera, fov));
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let n = self.keyframes.len();
+        if n == 0 {
+            return None;
+        }
+        let first = self.keyframes[0];
+        let last = self.keyframes[n - 1];
+        if n == 1 || time <= first.time {
+            return Some(first);
+        }
+        if time >= last.time {
+            return Some(last);
+        }
+        match self.interpolation {
+            Interpolation::CatmullRom => {
+                let i = self.keyframes
+                    .iter()
+                    .rposition(|k| k.time <= time)
+                    .unwrap_or(0)
+                    .min(n - 2);
+                let k1 = &self.keyframes[i];
+                let k2 = &self.keyframes[i + 1];
+                let k0 = &self.keyframes[if i == 0 { 0 } else { i - 1 }];
+                let k3 = &self.keyframes[(i + 2).min(n - 1)];
+                let span = k2.time - k1.time;
+                let t = if span > 0.0 { (time - k1.time) / span } else { 0.0 };
+                Some(CameraKeyframe {
+                    time,
+                    position: catmull_rom(k0.position, k1.position, k2.position, k3.position, t),
+                    target: catmull_rom(k0.target, k1.target, k2.target, k3.target, t),
+                    fov: catmull_rom_scalar(k0.fov, k1.fov, k2.fov, k3.fov, t),
+                })
+            }
+            Interpolation::Bezier => {
+                let t = (time - first.time) / (last.time - first.time);
+                let mut points = self.keyframes.clone();
+                for level in (1..n).rev() {
+                    for j in 0..level {
+                        points[j] = points[j].lerp(&points[j + 1], t);
+                    }
+                }
+                let mut result = points[0];
+                result.time = time;
+                Some(result)
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "# time px py pz tx ty tz fov")?;
+        if self.looping {
+            writeln!(out, "{} loop", self.interpolation.name())?;
+        } else {
+            writeln!(out, "{}", self.interpolation.name())?;
+        }
+        for k in &self.keyframes {
+            writeln!(out, "{} {} {} {} {} {} {} {}",
+                     k.time,
+                     k.position.x, k.position.y, k.position.z,
+                     k.target.x, k.target.y, k.target.z,
+                     k.fov)?;
+        }
+        out.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        CameraPath::read(io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(input: R) -> io::Result<Self> {
+        let mut result: Option<CameraPath> = None;
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // "<interpolation>" or "<interpolation> loop"
+            if result.is_none() {
+                let mut words = line.split_whitespace();
+                let name = words.next().unwrap_or("");
+                let interpolation = Interpolation::from_name(name)
+                    .ok_or_else(|| invalid_data(format!("unknown interpolation: {}", name)))?;
+                let mut loaded = CameraPath::new(interpolation);
+                match words.next() {
+                    Some("loop") => loaded.looping = true,
+                    Some(word) => return Err(invalid_data(format!("unexpected word: {}", word))),
+                    None => {}
+                }
+                result = Some(loaded);
+                continue;
+            }
+            let v = line
+                .split_whitespace()
+                .map(|x| x.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| invalid_data(e.to_string()))?;
+            if v.len() != 8 {
+                return Err(invalid_data(format!("expected 8 values, got {}", v.len())));
+            }
+            result.as_mut().unwrap().push(CameraKeyframe {
+                time: v[0],
+                position: cgmath::Vector3::new(v[1], v[2], v[3]),
+                target: cgmath::Vector3::new(v[4], v[5], v[6]),
+                fov: v[7],
+            });
+        }
+        result.ok_or_else(|| invalid_data("empty camera path".to_string()))
+    }
+}
+
+// advances on the fixed update step so a replay is identical every run
+pub struct CameraPathPlayer {
+    pub time: f64,
+    pub playing: bool,
+}
+
+impl CameraPathPlayer {
+    pub fn new() -> Self {
+        CameraPathPlayer {
+            time: 0.0,
+            playing: false,
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    // true on the step where playback reaches the end of a path that doesn't loop
+    pub fn advance(&mut self, dt: f64, path: &CameraPath) -> bool {
+        if !self.playing {
+            return false;
+        }
+        let duration = path.duration() as f64;
+        self.time += dt;
+        if self.time > duration {
+            if path.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn sample(&self, path: &CameraPath) -> Option<CameraKeyframe> {
+        let start = path.keyframes.first().map_or(0.0, |k| k.time);
+        path.sample(start + self.time as f32)
+    }
+}
+
+fn catmull_rom(p0: cgmath::Vector3<f32>,
+               p1: cgmath::Vector3<f32>,
+               p2: cgmath::Vector3<f32>,
+               p3: cgmath::Vector3<f32>,
+               t: f32,
+) -> cgmath::Vector3<f32> {
+    cgmath::Vector3::new(
+        catmull_rom_scalar(p0.x, p1.x, p2.x, p3.x, t),
+        catmull_rom_scalar(p0.y, p1.y, p2.y, p3.y, t),
+        catmull_rom_scalar(p0.z, p1.z, p2.z, p3.z, t),
+    )
+}
+
+fn catmull_rom_scalar(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f32, x: f32, y: f32, fov: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            position: cgmath::Vector3::new(x, y, 0.0),
+            target: cgmath::Vector3::new(x, y, -1.0),
+            fov,
+        }
+    }
+
+    // keyframes one second apart on a straight line, starting at `start`
+    fn line(interpolation: Interpolation, start: f32, n: usize) -> CameraPath {
+        let mut path = CameraPath::new(interpolation);
+        for i in 0..n {
+            let t = i as f32;
+            path.push(key(start + t, 2.0 * t, 0.0, 40.0 + 10.0 * t));
+        }
+        path
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn single_keyframes_hold_at_every_time() {
+        for &interpolation in &[Interpolation::CatmullRom, Interpolation::Bezier] {
+            let path = line(interpolation, 3.0, 1);
+            assert_eq!(path.duration(), 0.0);
+            for &time in &[-1.0, 3.0, 10.0] {
+                let k = path.sample(time).unwrap();
+                assert_eq!(k.time, 3.0);
+                assert_eq!(k.position, cgmath::Vector3::new(0.0, 0.0, 0.0));
+                assert_eq!(k.fov, 40.0);
+            }
+        }
+        assert!(CameraPath::new(Interpolation::CatmullRom).sample(0.0).is_none());
+    }
+
+    #[test]
+    fn sampling_outside_the_path_clamps_to_its_ends() {
+        for &interpolation in &[Interpolation::CatmullRom, Interpolation::Bezier] {
+            let mut path = line(interpolation, 1.0, 4);
+            for &looping in &[false, true] {
+                path.looping = looping;
+                assert_eq!(path.sample(0.0).unwrap().position.x, 0.0);
+                assert_eq!(path.sample(4.0).unwrap().position.x, 6.0);
+                assert_eq!(path.sample(9.0).unwrap().fov, 70.0);
+            }
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_keyframes() {
+        let path = line(Interpolation::CatmullRom, 0.0, 4);
+        for i in 0..4 {
+            let k = path.sample(i as f32).unwrap();
+            assert!(close(k.position.x, 2.0 * i as f32), "{:?}", k);
+        }
+        // evenly spaced points on a line stay on it between the keyframes
+        let k = path.sample(1.5).unwrap();
+        assert_eq!(k.time, 1.5);
+        assert!(close(k.position.x, 3.0), "{:?}", k);
+        assert!(close(k.target.z, -1.0), "{:?}", k);
+        assert!(close(k.fov, 55.0), "{:?}", k);
+    }
+
+    #[test]
+    fn bezier_uses_inner_keyframes_as_control_points() {
+        let mut path = CameraPath::new(Interpolation::Bezier);
+        path.push(key(0.0, 0.0, 0.0, 40.0));
+        path.push(key(2.0, 4.0, 0.0, 40.0));
+        path.push(key(1.0, 2.0, 4.0, 60.0));
+        let k = path.sample(1.0).unwrap();
+        assert!(close(k.position.x, 2.0), "{:?}", k);
+        assert!(close(k.position.y, 2.0), "{:?}", k);
+        assert!(close(k.fov, 50.0), "{:?}", k);
+    }
+
+    #[test]
+    fn players_stop_or_wrap_at_the_end() {
+        let mut path = line(Interpolation::CatmullRom, 5.0, 4);
+        let mut player = CameraPathPlayer::new();
+        assert!(!player.advance(1.0, &path));
+
+        player.play();
+        assert!(!player.advance(1.5, &path));
+        assert!(close(player.sample(&path).unwrap().position.x, 3.0));
+        assert!(player.advance(2.0, &path));
+        assert!(!player.playing);
+        assert_eq!(player.time, 3.0);
+        assert_eq!(player.sample(&path).unwrap().position.x, 6.0);
+
+        path.looping = true;
+        player.play();
+        assert!(!player.advance(4.5, &path));
+        assert!(player.playing);
+        assert_eq!(player.time, 1.5);
+        assert!(close(player.sample(&path).unwrap().position.x, 3.0));
+
+        // a single keyframe has nothing to loop over
+        let mut still = line(Interpolation::CatmullRom, 0.0, 1);
+        still.looping = true;
+        player.play();
+        assert!(player.advance(0.5, &still));
+        assert_eq!(player.time, 0.0);
+    }
+
+    #[test]
+    fn paths_round_trip_through_text() {
+        let mut path = line(Interpolation::Bezier, 0.5, 3);
+        path.looping = true;
+        let mut text = Vec::new();
+        path.write(&mut text).unwrap();
+
+        let loaded = CameraPath::read(&text[..]).unwrap();
+        assert_eq!(loaded.interpolation, Interpolation::Bezier);
+        assert!(loaded.looping);
+        assert_eq!(loaded.keyframes.len(), 3);
+        for (a, b) in path.keyframes.iter().zip(&loaded.keyframes) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.target, b.target);
+            assert_eq!(a.fov, b.fov);
+        }
+    }
+
+    #[test]
+    fn reading_sorts_keyframes_and_rejects_bad_lines() {
+        let text = "# comment\n\ncatmull-rom\n2 1 0 0 1 0 -1 50\n0 0 0 0 0 0 -1 40\n";
+        let loaded = CameraPath::read(text.as_bytes()).unwrap();
+        assert!(!loaded.looping);
+        assert_eq!(loaded.keyframes[0].time, 0.0);
+        assert_eq!(loaded.keyframes[1].time, 2.0);
+
+        for &bad in &["", "# only a comment\n", "spline\n", "bezier forever\n",
+                      "bezier\n0 0 0 0 0 0 -1\n", "bezier\n0 0 0 0 0 0 -1 x\n"] {
+            let err = CameraPath::read(bad.as_bytes()).err().expect(bad);
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+pub const MIN_TIME_SCALE: f64 = 0.0625;
+pub const MAX_TIME_SCALE: f64 = 4.0;
+// duration of a single step while paused
+pub const STEP_DT: f64 = 1.0 / 60.0;
+
+// simulation clock between the window's update events and the world
+pub struct SimClock {
+    pub paused: bool,
+    pub time_scale: f64,
+    step: bool,
+    time: f64,
+}
+
+impl SimClock {
+    pub fn new() -> Self {
+        SimClock {
+            paused: false,
+            time_scale: 1.0,
+            step: false,
+            time: 0.0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // advance one frame on the next tick; pauses the clock if it was running
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step = true;
+    }
+
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time_scale = scale.max(MIN_TIME_SCALE).min(MAX_TIME_SCALE);
+    }
+
+    pub fn slower(&mut self) {
+        let scale = self.time_scale / 2.0;
+        self.set_time_scale(scale);
+    }
+
+    pub fn faster(&mut self) {
+        let scale = self.time_scale * 2.0;
+        self.set_time_scale(scale);
+    }
+
+    // simulation time elapsed since start
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    // used when a session is restored at a given time
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    // scaled dt for the world, or None when nothing should advance this frame
+    pub fn tick(&mut self, dt: f64) -> Option<f64> {
+        let sim_dt = if self.step {
+            self.step = false;
+            STEP_DT * self.time_scale
+        } else if self.paused {
+            return None;
+        } else {
+            dt * self.time_scale
+        };
+        self.time += sim_dt;
+        Some(sim_dt)
+    }
+
+    pub fn status(&self) -> String {
+        if self.paused {
+            format!("paused x{}", self.time_scale)
+        } else {
+            format!("x{}", self.time_scale)
+        }
+    }
+}
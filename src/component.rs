@@ -0,0 +1,345 @@
+use cgmath::{self, prelude::*};
+use material::{self, VertexTexture};
+use mesh;
+use transform::Transform;
+use world::EntityId;
+
+// dense per-component storage indexed by entity slot; stale ids never match
+pub struct ComponentStore<T> {
+    items: Vec<Option<(u32, T)>>,
+}
+
+impl<T> ComponentStore<T> {
+    pub fn new() -> Self {
+        ComponentStore {
+            items: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, id: EntityId, value: T) -> Option<T> {
+        let index = id.index();
+        while self.items.len() <= index {
+            self.items.push(None);
+        }
+        let old = self.remove(id);
+        self.items[index] = Some((id.generation(), value));
+        old
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let matches = self.get(id).is_some();
+        if matches {
+            self.items[id.index()].take().map(|(_, value)| value)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.items
+            .get(id.index())
+            .and_then(|item| item.as_ref())
+            .filter(|item| item.0 == id.generation())
+            .map(|item| &item.1)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.items
+            .get_mut(id.index())
+            .and_then(|item| item.as_mut())
+            .filter(|item| item.0 == id.generation())
+            .map(|item| &mut item.1)
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item=(EntityId, &'a T)> + 'a {
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                item.as_ref().map(|item| (EntityId::new(i as u32, item.0), &item.1))
+            })
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item=(EntityId, &'a mut T)> + 'a {
+        self.items
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                item.as_mut().map(|item| (EntityId::new(i as u32, item.0), &mut item.1))
+            })
+    }
+}
+
+pub struct Renderable {
+    pub parts: Vec<mesh::Mesh<VertexTexture>>,
+}
+
+impl Renderable {
+    pub fn new(parts: Vec<mesh::Mesh<VertexTexture>>) -> Self {
+        Renderable { parts }
+    }
+
+    pub fn local_bounds(&self) -> Option<mesh::Aabb> {
+        mesh::parts_bounds(&self.parts)
+    }
+
+    pub fn material_params(&self) -> Option<material::MaterialParams> {
+        self.parts.iter().filter_map(|part| part.material.params()).next()
+    }
+
+    pub fn set_material_params(&mut self, params: material::MaterialParams) {
+        for part in self.parts.iter_mut() {
+            part.material.set_params(params);
+        }
+    }
+
+    pub fn edit_material_params(&mut self, edit: &Fn(&mut material::MaterialParams)) {
+        for part in self.parts.iter_mut() {
+            if let Some(mut params) = part.material.params() {
+                edit(&mut params);
+                part.material.set_params(params);
+            }
+        }
+    }
+
+    // bake a matrix into the parts, leaving the entity transform untouched
+    fn apply_to_parts(&mut self, mat: cgmath::Matrix4<f32>) {
+        for part in self.parts.iter_mut() {
+            part.model_view = mat * part.model_view;
+        }
+    }
+
+    pub fn recenter(&mut self) {
+        if let Some(bounds) = self.local_bounds() {
+            self.apply_to_parts(cgmath::Matrix4::from_translation(-bounds.center()));
+        }
+    }
+
+    // recentre and scale so the largest extent of the bounds is 1
+    pub fn normalize(&mut self) {
+        if let Some(bounds) = self.local_bounds() {
+            let size = bounds.size();
+            let extent = size.x.max(size.y).max(size.z);
+            let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+            self.apply_to_parts(
+                cgmath::Matrix4::from_scale(scale)
+                    * cgmath::Matrix4::from_translation(-bounds.center()));
+        }
+    }
+}
+
+// what an entity blocks in a fluid, in world space
+pub enum Obstacle {
+    // cross-section with the fluid's rest plane, closed outlines enclose the blocked area
+    Segments(Vec<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)>),
+    Bounds(mesh::Aabb),
+}
+
+// blocks fluids with the cross-section of the entity's renderable instead of
+// its bounds, so that only what actually stands in the water blocks it
+pub struct Collider {
+    // last cross-section as (model matrix, plane point, plane normal, segments);
+    // the triangles are only sliced again once the entity or the plane moves
+    slice: Option<(cgmath::Matrix4<f32>, cgmath::Vector3<f32>, cgmath::Vector3<f32>, Vec<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)>)>,
+}
+
+impl Collider {
+    pub fn new() -> Self {
+        Collider { slice: None }
+    }
+
+    // None when the parts drawn with `model` don't cross the world-space plane
+    pub fn obstacle(&mut self, renderable: &Renderable, model: cgmath::Matrix4<f32>, point: cgmath::Vector3<f32>, normal: cgmath::Vector3<f32>) -> Option<Obstacle> {
+        let bounds = renderable.local_bounds()?.transform(model);
+        let distances: Vec<f32> = bounds.corners().iter().map(|&c| (c - point).dot(normal)).collect();
+        if distances.iter().all(|&d| d < 0.0) || distances.iter().all(|&d| d >= 0.0) {
+            return None;
+        }
+        let cached = match self.slice {
+            Some((m, p, n, _)) => m == model && p == point && n == normal,
+            None => false,
+        };
+        if !cached {
+            let segments: Vec<_> = renderable.parts
+                .iter()
+                .flat_map(|part| part.geometry.slice(model * part.model_view, point, normal))
+                .collect();
+            self.slice = Some((model, point, normal, segments));
+        }
+        match self.slice {
+            Some((_, _, _, ref segments)) if !segments.is_empty() => Some(Obstacle::Segments(segments.clone())),
+            _ => None,
+        }
+    }
+}
+
+// placed by the entity's transform: point lights sit at its origin,
+// directional lights are rotated with it
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+    },
+    Point {
+        color: [f32; 3],
+        distance: f32,
+        decay: f32,
+    },
+}
+
+pub enum WorldLight {
+    Directional(material::DirectionalLight),
+    Point(material::PointLight),
+}
+
+impl Light {
+    pub fn to_world(&self, model: cgmath::Matrix4<f32>) -> WorldLight {
+        match *self {
+            Light::Directional { direction, color } => {
+                let dir = (model * cgmath::Vector3::from(direction).extend(0.0)).truncate();
+                let dir = if dir.magnitude2() > 0.0 { dir.normalize() } else { dir };
+                WorldLight::Directional(material::DirectionalLight::new(dir.into(), color))
+            }
+            Light::Point { color, distance, decay } => {
+                let position = model.w.truncate();
+                WorldLight::Point(material::PointLight::new(position.into(), color, distance, decay))
+            }
+        }
+    }
+}
+
+// per-entity behaviour driving the entity's own transform
+pub trait Simulation {
+    fn step(&mut self, transform: &mut Transform, dt: f64);
+}
+
+impl<F: FnMut(&mut Transform, f64)> Simulation for F {
+    fn step(&mut self, transform: &mut Transform, dt: f64) {
+        self(transform, dt)
+    }
+}
+
+// how an entity floats on a fluid surface
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FloatParams {
+    // depth of the entity's origin below the surface, in world units
+    pub draft: f32,
+    // pull towards the surface per unit of height, per second squared
+    pub stiffness: f32,
+    // vertical speed lost per second, so the bobbing settles
+    pub damping: f32,
+    // 0 stays upright, 1 follows the surface normal
+    pub tilt: f32,
+    // depth of the trough pushed into the surface per world unit moved
+    pub wake: f32,
+}
+
+impl Default for FloatParams {
+    fn default() -> Self {
+        FloatParams {
+            draft: 0.0,
+            stiffness: 30.0,
+            damping: 4.0,
+            tilt: 1.0,
+            wake: 0.5,
+        }
+    }
+}
+
+// opt-in behaviour moving an entity with the fluid surface under its footprint
+pub struct Buoyancy {
+    pub params: FloatParams,
+    // world units per second
+    velocity: f32,
+    // rotation before tilting, and the tilted one written by the last step; a
+    // rotation other than the written one was set elsewhere and becomes the new base
+    base: Option<cgmath::Quaternion<f32>>,
+    written: Option<cgmath::Quaternion<f32>>,
+    // world-space origin after the last step
+    last: Option<cgmath::Vector3<f32>>,
+}
+
+impl Buoyancy {
+    pub fn new(params: FloatParams) -> Self {
+        Buoyancy {
+            params,
+            velocity: 0.0,
+            base: None,
+            written: None,
+            last: None,
+        }
+    }
+
+    // springs the origin to the mean of the world-space (point, normal) surface
+    // samples and tilts it to their normal; returns the distance moved across
+    // the surface since the last step
+    pub fn step(&mut self, transform: &mut Transform, parent: cgmath::Matrix4<f32>, surface: &[(cgmath::Vector3<f32>, cgmath::Vector3<f32>)], dt: f64) -> f32 {
+        let inverse = match parent.invert() {
+            Some(inverse) => inverse,
+            None => return 0.0,
+        };
+        let dt = dt as f32;
+        let origin = (parent * transform.translation.extend(1.0)).truncate();
+        let moved = self.last.map_or(0.0, |last| {
+            let d = origin - last;
+            (d.x * d.x + d.z * d.z).sqrt()
+        });
+        self.last = Some(origin);
+        if surface.is_empty() {
+            self.velocity = 0.0;
+            return moved;
+        }
+
+        let level = surface.iter().map(|&(p, _)| p.y).sum::<f32>() / surface.len() as f32 - self.params.draft;
+        self.velocity += (self.params.stiffness * (level - origin.y) - self.params.damping * self.velocity) * dt;
+        let origin = cgmath::Vector3::new(origin.x, origin.y + self.velocity * dt, origin.z);
+        transform.translation = (inverse * origin.extend(1.0)).truncate();
+        self.last = Some(origin);
+
+        let base = match (self.base, self.written) {
+            (Some(base), Some(written)) if written == transform.rotation => base,
+            _ => transform.rotation,
+        };
+        self.base = Some(base);
+        let normal = surface.iter().fold(cgmath::Vector3::zero(), |sum, &(_, n)| sum + n);
+        let up = (inverse * normal.extend(0.0)).truncate();
+        if up.magnitude2() > 0.0 {
+            let tilted = cgmath::Quaternion::from_arc(cgmath::Vector3::unit_y(), up.normalize(), None) * base;
+            transform.rotation = base.slerp(tilted, self.params.tilt.max(0.0).min(1.0));
+        }
+        self.written = Some(transform.rotation);
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_generations_never_match() {
+        let mut store = ComponentStore::new();
+        let old = EntityId::new(2, 0);
+        let new = EntityId::new(2, 1);
+        assert!(store.insert(old, "old").is_none());
+        assert!(store.contains(old));
+        assert!(!store.contains(new));
+        assert!(store.get_mut(new).is_none());
+        // a stale id can neither remove nor replace the value of the current one
+        assert!(store.remove(new).is_none());
+        assert_eq!(store.insert(new, "new"), None);
+        assert!(!store.contains(old));
+        assert!(store.get(old).is_none());
+        assert!(store.remove(old).is_none());
+        assert_eq!(store.get(new), Some(&"new"));
+        assert_eq!(store.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![new]);
+        assert_eq!(store.remove(new), Some("new"));
+        assert_eq!(store.iter().count(), 0);
+    }
+}
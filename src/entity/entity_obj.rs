@@ -3,60 +3,18 @@ extern crate obj;
 
 use std;
 use std::path::{Path, PathBuf};
-use cgmath;
-use entity;
 use material::{self, Vertex, VertexTexture};
 use gfx;
 use mesh;
 use render;
-use piston_window;
 
-pub struct Mesh<V: gfx::traits::Pod> {
-    geometry: mesh::Geometry<V>,
-    material: Box<material::MaterialTrait<V>>,
-    model_view: cgmath::Matrix4<f32>,
-}
-
-pub struct EntityObj<V: gfx::traits::Pod> {
-    position: cgmath::Vector3<f32>,
-    model_view: cgmath::Matrix4<f32>,
-    parts: Vec<Mesh<V>>,
-}
-
-impl EntityObj<VertexTexture> {
-    pub fn from_obj(ctx: &mut render::RenderContext, name: &str) -> Self {
-        let path =
-            Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/models"))
-                .join(name);
-        let parts = load_wavefront(ctx, &path.as_path());
-        let position = cgmath::Vector3::new(0.0, 0.0, 0.0);
-        let model_view = cgmath::Matrix4::from_scale(0.01);
-        EntityObj {
-            position,
-            model_view,
-            parts,
-        }
-    }
-
-    pub fn set_pos(&mut self, pos: cgmath::Vector3<f32>) {
-        self.position = pos;
-        self.model_view.w = pos.extend(1.0);
-    }
-}
-
-impl<V: gfx::traits::Pod> entity::Entity for EntityObj<V> {
-    fn update(&mut self, dt: f64) {}
-
-    fn draw(&mut self, ctx: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder, dt: f64) {
-        for part in self.parts.iter_mut() {
-            part.material.draw(
-                ctx,
-                encoder,
-                &part.geometry,
-                self.model_view * part.model_view,
-            );
-        }
-    }
+// the parts of a model in assets/models, in the units and at the origin it was
+// authored with; see `Renderable::normalize`
+pub fn load_obj(ctx: &mut render::RenderContext, name: &str) -> Vec<mesh::Mesh<VertexTexture>> {
+    let path =
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/models"))
+            .join(name);
+    load_wavefront(ctx, &path.as_path())
 }
 
 pub fn convert_material(
@@ -77,7 +35,7 @@ pub fn convert_material(
     )
 }
 
-pub fn load_wavefront(ctx: &mut render::RenderContext, path: &Path) -> Vec<Mesh<VertexTexture>> {
+pub fn load_wavefront(ctx: &mut render::RenderContext, path: &Path) -> Vec<mesh::Mesh<VertexTexture>> {
     use self::genmesh::MapToVertices;
     let mut data: obj::Obj<obj::SimplePolygon> = obj::Obj::load(&path).unwrap();
     data.load_mtls();
@@ -119,11 +77,7 @@ pub fn load_wavefront(ctx: &mut render::RenderContext, path: &Path) -> Vec<Mesh<
                     .clone()
                     .map(|x| { convert_material(ctx, &x, &data.path.as_path()).unwrap() })
                     .unwrap();
-            let mesh = Mesh {
-                geometry,
-                material: Box::new(material),
-                model_view: cgmath::Matrix4::from_scale(1.0),
-            };
+            let mesh = mesh::Mesh::new(geometry, Box::new(material));
             vertex_data.push(mesh);
         }
     }
@@ -1,12 +1,26 @@
 use render;
 use piston_window;
 use gfx;
-use cgmath;
+use mesh;
+use transform::Transform;
+use material::MaterialParams;
 
 pub mod entity_obj;
-pub mod entity_fluid;
 
+// self-contained objects kept by the world's legacy adapter; fluids, models
+// and lights are built from components instead
 pub trait Entity {
     fn update(&mut self, dt: f64);
     fn draw(&mut self, ctx: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder, dt: f64);
-}
\ No newline at end of file
+    fn transform(&self) -> &Transform;
+    fn transform_mut(&mut self) -> &mut Transform;
+    fn bounds(&self) -> Option<mesh::Aabb> {
+        None
+    }
+    fn material_params(&self) -> Option<MaterialParams> {
+        None
+    }
+    fn set_material_params(&mut self, params: MaterialParams) {}
+    // changes fields on each part's own params, so parts keep the values that differ
+    fn edit_material_params(&mut self, edit: &Fn(&mut MaterialParams)) {}
+}
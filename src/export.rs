@@ -0,0 +1,221 @@
+use std;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use png::{self, HasParameters};
+use ron;
+use scene::SceneDesc;
+
+const RECIPE_KEYWORD: &str = "mg-rs:recipe";
+
+// everything needed to regenerate an exported image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recipe {
+    #[serde(default)]
+    pub scene_file: Option<String>,
+    // registered sketch the image came from; `scene` is used when it is unknown
+    #[serde(default)]
+    pub sketch: Option<String>,
+    pub scene: SceneDesc,
+    pub seed: u64,
+    // simulation time of the frame
+    pub time: f64,
+    // values applied on top of the scene, see `params::apply`
+    #[serde(default)]
+    pub params: BTreeMap<String, f32>,
+}
+
+impl Recipe {
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Recipe::from_png(&std::fs::read(path)?)
+    }
+
+    // from the bytes of a png written by `save_png`
+    pub fn from_png(bytes: &[u8]) -> io::Result<Self> {
+        let text = read_text_chunks(bytes)?
+            .into_iter()
+            .find(|&(ref keyword, _)| keyword == RECIPE_KEYWORD)
+            .map(|(_, text)| text)
+            .ok_or_else(|| invalid_data("no recipe in png".to_string()))?;
+        ron::de::from_str(&text).map_err(|e| invalid_data(e.to_string()))
+    }
+
+    // readable summary next to the full recipe, for image viewers
+    fn text_entries(&self) -> Vec<(String, String)> {
+        let camera = &self.scene.camera;
+        let mut entries = vec![
+            ("Software".to_string(), "mg-rs".to_string()),
+            ("Seed".to_string(), self.seed.to_string()),
+            ("Time".to_string(), self.time.to_string()),
+            ("Camera".to_string(), format!("{:?} position {:?} target {:?} fov {}", camera.mode, camera.position, camera.target, camera.fov)),
+        ];
+        if let Some(ref sketch) = self.sketch {
+            entries.push(("Sketch".to_string(), sketch.clone()));
+        }
+        if let Some(ref file) = self.scene_file {
+            entries.push(("Scene".to_string(), file.clone()));
+        }
+        for (key, value) in &self.params {
+            entries.push((format!("param:{}", key), value.to_string()));
+        }
+        entries
+    }
+}
+
+// RGBA8 pixels, top row first
+pub fn save_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8], recipe: &Recipe) -> io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    write_png(io::BufWriter::new(file), width, height, pixels, recipe)
+}
+
+pub fn write_png<W: Write>(out: W, width: u32, height: u32, pixels: &[u8], recipe: &Recipe) -> io::Result<()> {
+    let recipe_text = ron::ser::to_string_pretty(recipe, ron::ser::PrettyConfig::default())
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| invalid_data(e.to_string()))?;
+    for (keyword, text) in recipe.text_entries() {
+        writer.write_chunk(*b"iTXt", &itxt(&keyword, &text)).map_err(|e| invalid_data(e.to_string()))?;
+    }
+    writer.write_chunk(*b"iTXt", &itxt(RECIPE_KEYWORD, &recipe_text)).map_err(|e| invalid_data(e.to_string()))?;
+    writer.write_image_data(pixels).map_err(|e| invalid_data(e.to_string()))
+}
+
+// uncompressed iTXt: keyword, flags, empty language tag and translated keyword, utf-8 text
+fn itxt(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(keyword.len() + text.len() + 5);
+    data.write_all(keyword.as_bytes()).unwrap();
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.write_all(text.as_bytes()).unwrap();
+    data
+}
+
+// (keyword, text) of every uncompressed tEXt and iTXt chunk
+fn read_text_chunks(bytes: &[u8]) -> io::Result<Vec<(String, String)>> {
+    const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    if bytes.len() < 8 || bytes[..8] != SIGNATURE {
+        return Err(invalid_data("not a png file".to_string()));
+    }
+    let mut chunks = Vec::new();
+    let mut offset = 8;
+    while offset + 8 <= bytes.len() {
+        let length = bytes[offset..offset + 4].iter().fold(0usize, |n, &b| (n << 8) | b as usize);
+        let kind = &bytes[offset + 4..offset + 8];
+        let start = offset + 8;
+        let end = start + length;
+        if end + 4 > bytes.len() {
+            return Err(invalid_data("truncated png chunk".to_string()));
+        }
+        let data = &bytes[start..end];
+        match kind {
+            b"tEXt" => {
+                if let Some(i) = data.iter().position(|&b| b == 0) {
+                    // latin-1
+                    let keyword = data[..i].iter().map(|&b| b as char).collect();
+                    let text = data[i + 1..].iter().map(|&b| b as char).collect();
+                    chunks.push((keyword, text));
+                }
+            }
+            b"iTXt" => {
+                if let Some(entry) = parse_itxt(data) {
+                    chunks.push(entry);
+                }
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+        offset = end + 4;
+    }
+    Ok(chunks)
+}
+
+fn parse_itxt(data: &[u8]) -> Option<(String, String)> {
+    let keyword_end = data.iter().position(|&b| b == 0)?;
+    let compressed = *data.get(keyword_end + 1)? != 0;
+    if compressed {
+        return None;
+    }
+    let rest = data.get(keyword_end + 3..)?;
+    let language_end = rest.iter().position(|&b| b == 0)?;
+    let rest = &rest[language_end + 1..];
+    let translated_end = rest.iter().position(|&b| b == 0)?;
+    let keyword = String::from_utf8(data[..keyword_end].to_vec()).ok()?;
+    let text = String::from_utf8(rest[translated_end + 1..].to_vec()).ok()?;
+    Some((keyword, text))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::CameraMode;
+    use scene::CameraDesc;
+
+    fn recipe() -> Recipe {
+        let mut params = BTreeMap::new();
+        params.insert("water.roughness".to_string(), 0.25);
+        params.insert("camera.fov".to_string(), 60.0);
+        Recipe {
+            scene_file: Some("scenes/pool.ron".to_string()),
+            sketch: Some("water".to_string()),
+            scene: SceneDesc {
+                seed: Some(42),
+                background: [0.1, 0.2, 0.3, 1.0],
+                camera: CameraDesc {
+                    mode: CameraMode::Orbit,
+                    position: [0.0, 1.0, 2.0],
+                    target: [0.0, 0.0, 0.0],
+                    fov: 60.0,
+                },
+                lights: Vec::new(),
+                entities: Vec::new(),
+            },
+            seed: 42,
+            time: 2.5,
+            params,
+        }
+    }
+
+    fn png(recipe: Option<&Recipe>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let pixels = [255u8; 2 * 2 * 4];
+        match recipe {
+            Some(recipe) => write_png(&mut bytes, 2, 2, &pixels, recipe).unwrap(),
+            None => {
+                let mut encoder = png::Encoder::new(&mut bytes, 2, 2);
+                encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+                encoder.write_header().unwrap().write_image_data(&pixels).unwrap();
+            }
+        }
+        bytes
+    }
+
+    fn text(recipe: &Recipe) -> String {
+        ron::ser::to_string(recipe).unwrap()
+    }
+
+    #[test]
+    fn recipe_round_trips_through_png() {
+        let recipe = recipe();
+        let loaded = Recipe::from_png(&png(Some(&recipe))).unwrap();
+        assert_eq!(text(&loaded), text(&recipe));
+        assert_eq!(loaded.params["water.roughness"], 0.25);
+    }
+
+    #[test]
+    fn summary_entries_are_readable_chunks() {
+        let chunks = read_text_chunks(&png(Some(&recipe()))).unwrap();
+        assert!(chunks.iter().any(|&(ref k, ref t)| k == "Seed" && t == "42"));
+        assert!(chunks.iter().any(|&(ref k, ref t)| k == "param:water.roughness" && t == "0.25"));
+    }
+
+    #[test]
+    fn png_without_recipe_is_an_error() {
+        let err = Recipe::from_png(&png(None)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Recipe::from_png(b"not a png").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use super::{splash_cells, Boundary, FluidParams, HeightfieldSolver, SolverKind};
+
+// still-water depth in height units; gravity is picked so long waves travel at `wave_speed`
+const REST_DEPTH: f32 = 4.0;
+// cells a wave may cross per substep; the lattice itself moves one cell per substep
+const MAX_COURANT: f32 = 0.5;
+// relaxation times of the collision; 0.5 is inviscid and unstable, so some viscosity always remains
+const MIN_TAU: f32 = 0.55;
+const MAX_TAU: f32 = 2.0;
+// height of the swell driven in from the west edge
+const SWELL: f32 = 0.05;
+// below this depth a cell is treated as dry and at rest
+const MIN_DEPTH: f32 = 1e-3;
+
+// D2Q9 directions: rest, the four axes, then the diagonals
+const E: [[i32; 2]; 9] = [[0, 0], [1, 0], [0, 1], [-1, 0], [0, -1], [1, 1], [-1, 1], [-1, -1], [1, -1]];
+const OPPOSITE: [usize; 9] = [0, 3, 4, 1, 2, 7, 8, 5, 6];
+
+// shallow water lattice Boltzmann model: nine populations per cell whose
+// sum is the water depth and whose momentum is the flow. Disturbances
+// change the depth of the lattice cells directly, and solid cells bounce
+// populations back like the reflective edges do
+pub struct LbmSolver {
+    width: usize,
+    height: usize,
+    f: Vec<[f32; 9]>,
+    post: Vec<[f32; 9]>,
+    // depth and velocity in cells per substep, from the last collision
+    moments: Vec<[f32; 3]>,
+    heights: Vec<f32>,
+    solid: Vec<bool>,
+    // substeps per second of the last step, to report velocities in cells per second
+    rate: f32,
+    // lattice gravity of the last step, disturbances keep the equilibrium it defines
+    gravity: f32,
+    time: f64,
+    phase: f64,
+}
+
+impl LbmSolver {
+    pub fn new(width: usize, height: usize) -> Self {
+        let rest = equilibrium(REST_DEPTH, [0.0, 0.0], 0.0);
+        LbmSolver {
+            width,
+            height,
+            f: vec![rest; width * height],
+            post: vec![rest; width * height],
+            moments: vec![[REST_DEPTH, 0.0, 0.0]; width * height],
+            heights: vec![0.0; width * height],
+            solid: vec![false; width * height],
+            rate: 0.0,
+            gravity: 0.0,
+            time: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    // adds `amount` to the depth of a cell, keeping its flow
+    fn add_depth(&mut self, i: usize, amount: f32) {
+        let (depth, u) = macroscopic(&self.f[i]);
+        let before = equilibrium(depth, u, self.gravity);
+        let after = equilibrium(depth + amount, u, self.gravity);
+        for k in 0..9 {
+            self.f[i][k] += after[k] - before[k];
+        }
+    }
+
+    fn substep(&mut self, dt: f32, params: &FluidParams, g: f32) {
+        let (w, h) = (self.width, self.height);
+        let keep = params.damping.max(0.0).min(1.0).powf(dt);
+        // lattice viscosity is (2 tau - 1) / 6 cells squared per substep
+        let tau = (0.5 + 3.0 * params.viscosity.max(0.0) * dt).max(MIN_TAU).min(MAX_TAU);
+        let omega = 1.0 / tau;
+
+        // relax towards equilibrium, with the damping applied to the target
+        {
+            let (f, solid) = (&self.f, &self.solid);
+            self.post.par_chunks_mut(w).zip(self.moments.par_chunks_mut(w)).enumerate().for_each(|(z, (post, moments))| {
+                for x in 0..w {
+                    let i = z * w + x;
+                    if solid[i] {
+                        continue;
+                    }
+                    let (depth, u) = macroscopic(&f[i]);
+                    let current = equilibrium(depth, u, g);
+                    let (depth, u) = (REST_DEPTH + (depth - REST_DEPTH) * keep, [u[0] * keep, u[1] * keep]);
+                    let target = equilibrium(depth, u, g);
+                    for k in 0..9 {
+                        post[x][k] = target[k] + (1.0 - omega) * (f[i][k] - current[k]);
+                    }
+                    moments[x] = [depth, u[0], u[1]];
+                }
+            });
+        }
+
+        // pull each population from the cell it comes from
+        let (post, moments, solid) = (&self.post, &self.moments, &self.solid);
+        let boundary = params.boundary;
+        self.f.par_chunks_mut(w).enumerate().for_each(|(z, row)| {
+            for x in 0..w {
+                let i = z * w + x;
+                if solid[i] {
+                    continue;
+                }
+                for k in 0..9 {
+                    let (sx, sz) = (x as i32 - E[k][0], z as i32 - E[k][1]);
+                    let inside = sx >= 0 && sz >= 0 && sx < w as i32 && sz < h as i32;
+                    let source = if inside {
+                        Some(sz as usize * w + sx as usize)
+                    } else if boundary == Boundary::Periodic {
+                        let (sx, sz) = ((sx + w as i32) % w as i32, (sz + h as i32) % h as i32);
+                        Some(sz as usize * w + sx as usize)
+                    } else {
+                        None
+                    };
+                    row[x][k] = match source {
+                        Some(j) if !solid[j] => post[j][k],
+                        // bounce back off an obstacle or a reflective wall
+                        Some(_) => post[i][OPPOSITE[k]],
+                        None if boundary == Boundary::Reflective => post[i][OPPOSITE[k]],
+                        // the outside looks like this cell, so nothing comes back
+                        None => {
+                            let m = moments[i];
+                            equilibrium(m[0], [m[1], m[2]], g)[k]
+                        }
+                    };
+                }
+            }
+        });
+    }
+}
+
+// depth and velocity of a cell's populations
+fn macroscopic(f: &[f32; 9]) -> (f32, [f32; 2]) {
+    let depth: f32 = f.iter().sum();
+    if depth < MIN_DEPTH {
+        return (depth, [0.0, 0.0]);
+    }
+    let mut momentum = [0.0, 0.0];
+    for k in 1..9 {
+        momentum[0] += E[k][0] as f32 * f[k];
+        momentum[1] += E[k][1] as f32 * f[k];
+    }
+    (depth, [momentum[0] / depth, momentum[1] / depth])
+}
+
+// Zhou's shallow water equilibrium with a lattice speed of one cell per substep
+fn equilibrium(h: f32, u: [f32; 2], g: f32) -> [f32; 9] {
+    let uu = u[0] * u[0] + u[1] * u[1];
+    let mut f = [0.0; 9];
+    f[0] = h - 5.0 * g * h * h / 6.0 - 2.0 * h * uu / 3.0;
+    for k in 1..9 {
+        let eu = E[k][0] as f32 * u[0] + E[k][1] as f32 * u[1];
+        let term = g * h * h / 6.0 + h * eu / 3.0 + h * eu * eu / 2.0 - h * uu / 6.0;
+        f[k] = if k < 5 { term } else { term / 4.0 };
+    }
+    f
+}
+
+impl HeightfieldSolver for LbmSolver {
+    fn kind(&self) -> SolverKind {
+        SolverKind::Lbm
+    }
+
+    fn step(&mut self, dt: f32, params: &FluidParams) {
+        let substeps = (params.wave_speed.max(0.0) * dt / MAX_COURANT).ceil().max(1.0) as u32;
+        let sub_dt = dt / substeps as f32;
+        let c = params.wave_speed.max(0.0) * sub_dt;
+        let g = c * c / REST_DEPTH;
+        self.rate = 1.0 / sub_dt;
+        self.gravity = g;
+
+        // the swell raises and lowers the west column by the change of a sine since the last step
+        if params.ambient_wave != 0.0 {
+            let before = (self.time * params.ambient_wave + self.phase).sin();
+            let after = ((self.time + dt as f64) * params.ambient_wave + self.phase).sin();
+            let amount = SWELL * (after - before) as f32;
+            for z in 0..self.height {
+                let i = z * self.width;
+                if !self.solid[i] {
+                    self.add_depth(i, amount);
+                }
+            }
+        }
+        self.time += dt as f64;
+
+        for _ in 0..substeps {
+            self.substep(sub_dt, params, g);
+        }
+
+        for (i, h) in self.heights.iter_mut().enumerate() {
+            *h = if self.solid[i] { 0.0 } else { macroscopic(&self.f[i]).0 - REST_DEPTH };
+        }
+    }
+
+    fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    // shows up in `heights` after the next step
+    fn disturb(&mut self, x: f32, z: f32, radius: f32, amount: f32) {
+        let (w, h) = (self.width, self.height);
+        splash_cells(w, h, x, z, radius, amount, |i, value| {
+            if !self.solid[i] {
+                self.add_depth(i, value);
+            }
+        });
+    }
+
+    // cells an obstacle leaves fill back up with still water
+    fn set_solid(&mut self, solid: &[bool]) {
+        let rest = equilibrium(REST_DEPTH, [0.0, 0.0], self.gravity);
+        for (i, &now) in solid.iter().enumerate() {
+            if self.solid[i] && !now {
+                self.f[i] = rest;
+                self.moments[i] = [REST_DEPTH, 0.0, 0.0];
+            }
+        }
+        self.solid.copy_from_slice(solid);
+    }
+
+    fn velocity(&self, x: usize, z: usize) -> Option<[f32; 2]> {
+        if x >= self.width || z >= self.height {
+            return None;
+        }
+        let m = self.moments[z * self.width + x];
+        Some([m[1] * self.rate, m[2] * self.rate])
+    }
+
+    fn randomize(&mut self, rng: &mut RngCore) {
+        self.phase = rng.gen_range(0.0, 100.0);
+    }
+}
@@ -0,0 +1,344 @@
+use std::f32::consts::PI;
+use rand::{Rng, RngCore};
+use rand::prng::ChaChaRng;
+use rayon::prelude::*;
+
+mod wave;
+mod shallow_water;
+mod lbm;
+pub mod worker;
+pub mod surface;
+
+pub use self::wave::WaveSolver;
+pub use self::shallow_water::ShallowWaterSolver;
+pub use self::lbm::LbmSolver;
+pub use self::surface::{Disturbance, FluidSurface};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Boundary {
+    Reflective,
+    Absorbing,
+    Periodic,
+}
+
+impl Boundary {
+    pub fn next(self) -> Self {
+        match self {
+            Boundary::Reflective => Boundary::Absorbing,
+            Boundary::Absorbing => Boundary::Periodic,
+            Boundary::Periodic => Boundary::Reflective,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SolverKind {
+    // damped linear wave equation; cheap and stable
+    Wave,
+    // height and velocity with nonlinear transport
+    ShallowWater,
+    // shallow water lattice Boltzmann model
+    Lbm,
+}
+
+impl Default for SolverKind {
+    fn default() -> Self {
+        SolverKind::Lbm
+    }
+}
+
+// positions are surface coordinates, (0, 0) to (1, 1) across the grid
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum WaveSource {
+    // a drop every `interval` seconds
+    Point {
+        at: [f32; 2],
+        strength: f32,
+        radius: f32,
+        interval: f32,
+    },
+    // a line held at `amplitude * sin(2 pi frequency t)`, sending out plane waves
+    Line {
+        from: [f32; 2],
+        to: [f32; 2],
+        amplitude: f32,
+        frequency: f32,
+    },
+    // drops at random places, `rate` per second on average
+    Rain {
+        rate: f32,
+        strength: f32,
+        radius: f32,
+    },
+    // a single oscillating point
+    Sinusoid {
+        at: [f32; 2],
+        amplitude: f32,
+        frequency: f32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluidParams {
+    // surface displacement relative to the entity's scale
+    pub height_scale: f32,
+    // in cells per second; solvers limit or substep it to stay stable
+    pub wave_speed: f32,
+    // in cells squared per second
+    pub viscosity: f32,
+    // fraction of the motion kept per second
+    pub damping: f32,
+    // rate in radians per second of a swell the LBM solver drives in from the west edge; 0 switches it off
+    pub ambient_wave: f64,
+    pub boundary: Boundary,
+    pub sources: Vec<WaveSource>,
+    // fixed solver steps per second of simulation time, whatever the frame rate
+    pub substep_rate: f64,
+    // time beyond this many steps in one update is dropped instead of caught up
+    pub max_substeps: u32,
+    // blend the last two steps for drawing, one step behind the solver
+    pub interpolate: bool,
+    // step on a worker thread; drawing then picks up its latest results
+    pub threaded: bool,
+}
+
+impl Default for FluidParams {
+    fn default() -> Self {
+        FluidParams {
+            height_scale: 0.25,
+            wave_speed: 60.0,
+            viscosity: 0.0,
+            damping: 0.55,
+            ambient_wave: 1.2,
+            boundary: Boundary::Reflective,
+            sources: Vec::new(),
+            substep_rate: 120.0,
+            max_substeps: 8,
+            interpolate: true,
+            threaded: true,
+        }
+    }
+}
+
+// simulates a FluidSurface on a grid with one cell per vertex
+pub trait HeightfieldSolver: Send {
+    fn kind(&self) -> SolverKind;
+    // advance by `dt` seconds
+    fn step(&mut self, dt: f32, params: &FluidParams);
+    // surface displacement per cell, row by row
+    fn heights(&self) -> &[f32];
+    // gaussian bump around a cell position, `radius` in cells
+    fn disturb(&mut self, x: f32, z: f32, radius: f32, amount: f32);
+    // cells covered by obstacles; held flat so waves reflect off them
+    fn set_solid(&mut self, solid: &[bool]);
+    // flow in cells per second, for solvers that track it
+    fn velocity(&self, x: usize, z: usize) -> Option<[f32; 2]> {
+        None
+    }
+    fn randomize(&mut self, rng: &mut RngCore) {}
+}
+
+pub fn create(kind: SolverKind, width: usize, height: usize) -> Box<HeightfieldSolver> {
+    match kind {
+        SolverKind::Wave => Box::new(WaveSolver::new(width, height)),
+        SolverKind::ShallowWater => Box::new(ShallowWaterSolver::new(width, height)),
+        SolverKind::Lbm => Box::new(LbmSolver::new(width, height)),
+    }
+}
+
+pub fn splash(field: &mut [f32], width: usize, height: usize, cx: f32, cz: f32, radius: f32, amount: f32) {
+    splash_cells(width, height, cx, cz, radius, amount, |i, value| field[i] += value);
+}
+
+// the cells of a gaussian bump and the amount each receives
+pub fn splash_cells<F: FnMut(usize, f32)>(width: usize, height: usize, cx: f32, cz: f32, radius: f32, amount: f32, mut add: F) {
+    let radius = radius.max(1.0);
+    let reach = (radius * 2.0).ceil() as i32;
+    for dz in -reach..reach + 1 {
+        for dx in -reach..reach + 1 {
+            let x = cx.round() as i32 + dx;
+            let z = cz.round() as i32 + dz;
+            if x < 0 || z < 0 || x >= width as i32 || z >= height as i32 {
+                continue;
+            }
+            let d2 = (x as f32 - cx).powi(2) + (z as f32 - cz).powi(2);
+            add(z as usize * width + x as usize, amount * (-d2 / (radius * radius)).exp());
+        }
+    }
+}
+
+// central differences, one-sided at the edges, a row per task;
+// `spacing` is the cell size in height units
+pub fn gradients(heights: &[f32], width: usize, height: usize, spacing: f32, out: &mut [[f32; 2]]) {
+    out.par_chunks_mut(width).enumerate().for_each(|(z, row)| {
+        let (z0, z1) = (z.max(1) - 1, (z + 1).min(height - 1));
+        for (x, g) in row.iter_mut().enumerate() {
+            let (x0, x1) = (x.max(1) - 1, (x + 1).min(width - 1));
+            g[0] = (heights[z * width + x1] - heights[z * width + x0]) / ((x1 - x0).max(1) as f32 * spacing);
+            g[1] = (heights[z1 * width + x] - heights[z0 * width + x]) / ((z1 - z0).max(1) as f32 * spacing);
+        }
+    });
+}
+
+// drives the wave sources of one surface; rain falls from its own random stream
+pub struct Emitter {
+    rain: f32,
+    rng: ChaChaRng,
+}
+
+impl Emitter {
+    pub fn new(rng: ChaChaRng) -> Self {
+        Emitter { rain: 0.0, rng }
+    }
+
+    // one step ending at `time`; oscillating sources add the change of their
+    // value since the last step, so they work with any solver
+    pub fn emit(&mut self, sources: &[WaveSource], solver: &mut HeightfieldSolver, width: usize, height: usize, time: f32, dt: f32) {
+        let (sx, sz) = ((width - 1) as f32, (height - 1) as f32);
+        let wave = |amplitude: f32, frequency: f32| {
+            amplitude * ((2.0 * PI * frequency * time).sin() - (2.0 * PI * frequency * (time - dt)).sin())
+        };
+        for source in sources {
+            match *source {
+                WaveSource::Point { at, strength, radius, interval } => {
+                    let interval = interval.max(dt);
+                    if ((time - dt) / interval).floor() != (time / interval).floor() {
+                        solver.disturb(at[0] * sx, at[1] * sz, radius, strength);
+                    }
+                }
+                WaveSource::Line { from, to, amplitude, frequency } => {
+                    let amount = wave(amplitude, frequency);
+                    let (a, b) = ([from[0] * sx, from[1] * sz], [to[0] * sx, to[1] * sz]);
+                    let steps = ((b[0] - a[0]).abs().max((b[1] - a[1]).abs()).ceil() as u32).max(1);
+                    for s in 0..steps + 1 {
+                        let t = s as f32 / steps as f32;
+                        solver.disturb(a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, 1.0, amount);
+                    }
+                }
+                WaveSource::Rain { rate, strength, radius } => {
+                    self.rain += rate * dt;
+                    while self.rain >= 1.0 {
+                        self.rain -= 1.0;
+                        let (x, z) = (self.rng.gen_range(0.0, sx), self.rng.gen_range(0.0, sz));
+                        solver.disturb(x, z, radius, -strength);
+                    }
+                }
+                WaveSource::Sinusoid { at, amplitude, frequency } => {
+                    solver.disturb(at[0] * sx, at[1] * sz, 1.0, wave(amplitude, frequency));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SolverKind; 3] = [SolverKind::Wave, SolverKind::ShallowWater, SolverKind::Lbm];
+    const BOUNDARIES: [Boundary; 3] = [Boundary::Reflective, Boundary::Absorbing, Boundary::Periodic];
+    const SIZE: usize = 24;
+    const DT: f32 = 1.0 / 120.0;
+
+    // the swell would move an otherwise still LBM surface
+    fn still(boundary: Boundary) -> FluidParams {
+        FluidParams {
+            ambient_wave: 0.0,
+            boundary,
+            ..FluidParams::default()
+        }
+    }
+
+    fn largest(heights: &[f32]) -> f32 {
+        heights.iter().fold(0.0, |m, h| m.max(h.abs()))
+    }
+
+    #[test]
+    fn flat_surfaces_stay_flat() {
+        for &kind in &KINDS {
+            for &boundary in &BOUNDARIES {
+                let mut solver = create(kind, SIZE, SIZE);
+                assert_eq!(solver.kind(), kind);
+                let params = still(boundary);
+                for _ in 0..200 {
+                    solver.step(DT, &params);
+                }
+                assert_eq!(solver.heights().len(), SIZE * SIZE);
+                let h = largest(solver.heights());
+                assert!(h < 1e-4, "{:?} {:?} moved by {}", kind, boundary, h);
+            }
+        }
+    }
+
+    #[test]
+    fn disturbances_stay_local_for_a_step() {
+        let params = still(Boundary::Reflective);
+        let c = SIZE as f32 / 2.0;
+        for &kind in &KINDS {
+            let mut solver = create(kind, SIZE, SIZE);
+            solver.step(DT, &params);
+            solver.disturb(c, c, 2.0, 1.0);
+            // the lattice solver only shows disturbances after a step
+            solver.step(DT, &params);
+            let heights = solver.heights();
+            let center = heights[SIZE / 2 * SIZE + SIZE / 2];
+            assert!(center > 0.5, "{:?} center at {}", kind, center);
+            for &i in &[0, SIZE - 1, SIZE * (SIZE - 1), SIZE * SIZE - 1] {
+                assert!(heights[i].abs() < 1e-5, "{:?} corner {} at {}", kind, i, heights[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn every_boundary_stays_stable() {
+        let c = SIZE as f32 / 2.0;
+        for &kind in &KINDS {
+            for &boundary in &BOUNDARIES {
+                let mut solver = create(kind, SIZE, SIZE);
+                let params = still(boundary);
+                solver.step(DT, &params);
+                solver.disturb(c, c, 3.0, 1.0);
+                // an edge splash sends waves straight into the boundary
+                solver.disturb(0.0, c, 2.0, 0.5);
+                for step in 0..2000 {
+                    solver.step(DT, &params);
+                    let h = largest(solver.heights());
+                    assert!(h.is_finite() && h < 8.0, "{:?} {:?} reached {} at step {}", kind, boundary, h, step);
+                }
+                for z in 0..SIZE {
+                    for x in 0..SIZE {
+                        if let Some(v) = solver.velocity(x, z) {
+                            assert!(v[0].is_finite() && v[1].is_finite(), "{:?} {:?}", kind, boundary);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solid_cells_stay_flat() {
+        let mut solid = vec![false; SIZE * SIZE];
+        for z in 8..12 {
+            for x in 8..12 {
+                solid[z * SIZE + x] = true;
+            }
+        }
+        let params = still(Boundary::Reflective);
+        for &kind in &KINDS {
+            let mut solver = create(kind, SIZE, SIZE);
+            solver.set_solid(&solid);
+            solver.step(DT, &params);
+            solver.disturb(6.0, 6.0, 2.0, 1.0);
+            for _ in 0..100 {
+                solver.step(DT, &params);
+            }
+            for (i, &h) in solver.heights().iter().enumerate() {
+                if solid[i] {
+                    assert_eq!(h, 0.0, "{:?} solid cell {}", kind, i);
+                }
+            }
+        }
+    }
+}
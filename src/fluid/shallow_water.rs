@@ -0,0 +1,162 @@
+use super::{splash, Boundary, FluidParams, HeightfieldSolver, SolverKind};
+
+// still-water depth in cells; gravity is picked so long waves travel at `wave_speed`
+const REST_DEPTH: f32 = 4.0;
+// cells a wave may cross per substep
+const MAX_COURANT: f32 = 0.5;
+const MAX_VISCOSITY: f32 = 0.2;
+
+// shallow water equations on a staggered grid: surface height per cell,
+// velocities on the faces between cells, depth carried upwind
+pub struct ShallowWaterSolver {
+    width: usize,
+    height: usize,
+    eta: Vec<f32>,
+    // across x, width + 1 faces per row
+    u: Vec<f32>,
+    // across z, height + 1 rows of faces
+    v: Vec<f32>,
+    scratch: Vec<f32>,
+    solid: Vec<bool>,
+}
+
+impl ShallowWaterSolver {
+    pub fn new(width: usize, height: usize) -> Self {
+        ShallowWaterSolver {
+            width,
+            height,
+            eta: vec![0.0; width * height],
+            u: vec![0.0; (width + 1) * height],
+            v: vec![0.0; width * (height + 1)],
+            scratch: Vec::with_capacity((width + 1) * (height + 1)),
+            solid: vec![false; width * height],
+        }
+    }
+
+    fn substep(&mut self, dt: f32, params: &FluidParams) {
+        let (w, h) = (self.width, self.height);
+        let c = params.wave_speed.max(0.0);
+        let g = c * c / REST_DEPTH;
+        let keep = params.damping.max(0.0).min(1.0).powf(dt);
+        let periodic = params.boundary == Boundary::Periodic;
+        let absorbing = params.boundary == Boundary::Absorbing;
+        // outgoing long waves carry this much velocity per unit of height
+        let outflow = c / REST_DEPTH;
+
+        if params.viscosity > 0.0 {
+            let k = (params.viscosity * dt).min(MAX_VISCOSITY);
+            diffuse(&mut self.u, &mut self.scratch, w + 1, h, k);
+            diffuse(&mut self.v, &mut self.scratch, w, h + 1, k);
+        }
+
+        // accelerate down the height gradient
+        for z in 0..h {
+            for x in 0..w + 1 {
+                let (west, east) = if periodic { ((x + w - 1) % w, x % w) } else { (x.max(1) - 1, x.min(w - 1)) };
+                let (a, b) = (z * w + west, z * w + east);
+                let i = z * (w + 1) + x;
+                self.u[i] = if self.solid[a] || self.solid[b] {
+                    0.0
+                } else if !periodic && x == 0 {
+                    if absorbing { -outflow * self.eta[b] } else { 0.0 }
+                } else if !periodic && x == w {
+                    if absorbing { outflow * self.eta[a] } else { 0.0 }
+                } else {
+                    (self.u[i] - g * dt * (self.eta[b] - self.eta[a])) * keep
+                };
+            }
+        }
+        for z in 0..h + 1 {
+            for x in 0..w {
+                let (north, south) = if periodic { ((z + h - 1) % h, z % h) } else { (z.max(1) - 1, z.min(h - 1)) };
+                let (a, b) = (north * w + x, south * w + x);
+                let i = z * w + x;
+                self.v[i] = if self.solid[a] || self.solid[b] {
+                    0.0
+                } else if !periodic && z == 0 {
+                    if absorbing { -outflow * self.eta[b] } else { 0.0 }
+                } else if !periodic && z == h {
+                    if absorbing { outflow * self.eta[a] } else { 0.0 }
+                } else {
+                    (self.v[i] - g * dt * (self.eta[b] - self.eta[a])) * keep
+                };
+            }
+        }
+
+        // move water with the face fluxes
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.eta);
+        let eta = &self.scratch;
+        for z in 0..h {
+            for x in 0..w {
+                let i = z * w + x;
+                if self.solid[i] {
+                    self.eta[i] = 0.0;
+                    continue;
+                }
+                let (west, east) = if periodic { ((x + w - 1) % w, (x + 1) % w) } else { (x.max(1) - 1, (x + 1).min(w - 1)) };
+                let (north, south) = if periodic { ((z + h - 1) % h, (z + 1) % h) } else { (z.max(1) - 1, (z + 1).min(h - 1)) };
+                let row = z * (w + 1);
+                let flux_x = upwind(self.u[row + x + 1], eta[i], eta[z * w + east])
+                    - upwind(self.u[row + x], eta[z * w + west], eta[i]);
+                let flux_z = upwind(self.v[(z + 1) * w + x], eta[i], eta[south * w + x])
+                    - upwind(self.v[z * w + x], eta[north * w + x], eta[i]);
+                self.eta[i] -= dt * (flux_x + flux_z);
+            }
+        }
+    }
+}
+
+// flux through a face with velocity `q` between cells of height `a` and `b`
+fn upwind(q: f32, a: f32, b: f32) -> f32 {
+    q * (REST_DEPTH + if q > 0.0 { a } else { b })
+}
+
+// explicit diffusion of the interior of a row-major field
+fn diffuse(field: &mut [f32], scratch: &mut Vec<f32>, width: usize, height: usize, k: f32) {
+    scratch.clear();
+    scratch.extend_from_slice(field);
+    for z in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let i = z * width + x;
+            field[i] = scratch[i] + k * (scratch[i - 1] + scratch[i + 1] + scratch[i - width] + scratch[i + width] - 4.0 * scratch[i]);
+        }
+    }
+}
+
+impl HeightfieldSolver for ShallowWaterSolver {
+    fn kind(&self) -> SolverKind {
+        SolverKind::ShallowWater
+    }
+
+    fn step(&mut self, dt: f32, params: &FluidParams) {
+        let substeps = (params.wave_speed.max(0.0) * dt / MAX_COURANT).ceil().max(1.0) as u32;
+        for _ in 0..substeps {
+            self.substep(dt / substeps as f32, params);
+        }
+    }
+
+    fn heights(&self) -> &[f32] {
+        &self.eta
+    }
+
+    fn disturb(&mut self, x: f32, z: f32, radius: f32, amount: f32) {
+        splash(&mut self.eta, self.width, self.height, x, z, radius, amount);
+    }
+
+    fn set_solid(&mut self, solid: &[bool]) {
+        self.solid.copy_from_slice(solid);
+    }
+
+    // average of the two faces on each axis
+    fn velocity(&self, x: usize, z: usize) -> Option<[f32; 2]> {
+        if x >= self.width || z >= self.height {
+            return None;
+        }
+        let (w, row) = (self.width, z * (self.width + 1));
+        Some([
+            (self.u[row + x] + self.u[row + x + 1]) * 0.5,
+            (self.v[z * w + x] + self.v[(z + 1) * w + x]) * 0.5,
+        ])
+    }
+}
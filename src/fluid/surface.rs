@@ -0,0 +1,517 @@
+use cgmath;
+use cgmath::prelude::*;
+use piston_window;
+use render::{self};
+use mesh;
+use material::{self, MaterialTrait, Vertex};
+use gfx;
+use std;
+use scene;
+use component::Obstacle;
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use fluid::{self, FluidParams, SolverKind};
+use fluid::worker::{Command, FluidSim, Frame, SolverThread};
+
+// step batches queued on the worker before update waits for it
+const MAX_IN_FLIGHT: u64 = 2;
+// displacement pushed aside when an obstacle moves into or out of a cell
+const WAKE_STRENGTH: f32 = 0.05;
+
+fn make_flatmesh(width: f32, height: f32, div_w: u32, div_h: u32) -> mesh::Geometry<material::VertexTexture> {
+    let mut vertices = Vec::with_capacity((div_h + 1) as usize * (div_w + 1) as usize);
+    let dw = width / div_w as f32;
+    let dh = height / div_h as f32;
+    for z0 in 0..div_h + 1 {
+        for x0 in 0..div_w + 1 {
+            vertices.push(material::VertexTexture::new(
+                [x0 as f32 * dw - (width / 2.0), 0.0, z0 as f32 * dh - (height / 2.0), ],
+                [0.0, 0.0],
+                [0.0, 1.0, 0.0, ],
+            ));
+        }
+    }
+    let mut faces = Vec::with_capacity(div_h as usize * div_w as usize * 6);
+    for z0 in 0..div_h {
+        faces.push(z0 * (div_w + 1));
+        for x0 in 0..div_w + 1 {
+            let offset = x0 + z0 * (div_w + 1);
+            faces.push(offset);
+            faces.push(offset + div_w + 1);
+        }
+        faces.push((z0 + 2) * (div_w + 1) - 1);
+    }
+    //let indices = glium::index::IndexBuffer::new(display, glium::index::PrimitiveType::TriangleStrip, &faces).unwrap();
+    mesh::Geometry { vertices, indices: faces }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Disturbance {
+    // displacement at the centre, in units of the surface height
+    pub strength: f32,
+    // world-space radius of the affected area
+    pub radius: f32,
+}
+
+// world-space rest plane (point, normal) of a surface drawn with `model`
+pub fn rest_plane(model: cgmath::Matrix4<f32>) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    let normal = (model * cgmath::Vector4::new(0.0, 1.0, 0.0, 0.0)).truncate();
+    (model.w.truncate(), normal.normalize())
+}
+
+// component of an entity whose surface is simulated; placed by the entity's
+// world matrix, which every method taking `model` expects
+pub struct FluidSurface {
+    x: u32,
+    y: u32,
+    height_mag: f32,
+    width: f32,
+    params: FluidParams,
+    kind: SolverKind,
+    // the simulation lives here, or on `thread` while it runs there
+    sim: Option<FluidSim>,
+    thread: Option<SolverThread>,
+    // results being drawn
+    front: Frame,
+    solid: Vec<bool>,
+    geometry: mesh::Geometry<material::VertexTexture>,
+    material: material::MaterialWater,
+    bounds: Option<mesh::Aabb>,
+    time: f64,
+    pending: f64,
+    has_obstacles: bool,
+}
+
+impl FluidSurface {
+    pub fn new(ctx: &mut render::RenderContext, x: u32, y: u32, scale: f32) -> Self {
+        FluidSurface::with_params(ctx, x, y, scale, FluidParams::default())
+    }
+
+    pub fn with_params(ctx: &mut render::RenderContext, x: u32, y: u32, scale: f32, params: FluidParams) -> Self {
+        FluidSurface::with_solver(ctx, x, y, scale, SolverKind::default(), params)
+    }
+
+    pub fn with_solver(ctx: &mut render::RenderContext, x: u32, y: u32, scale: f32, solver: SolverKind, params: FluidParams) -> Self {
+        let dx = scale / x as f32;
+        let cells = (x as usize + 1) * (y as usize + 1);
+        let (vertices, indices) = test_data();
+        let geometry = make_flatmesh(x as f32 * dx, y as f32 * dx, x, y);
+        let material = material::MaterialWater::new(
+            ctx,
+            material::WaterParams::default(),
+            gfx::Primitive::TriangleStrip,
+        ).unwrap();
+        let bounds = geometry.bounds();
+        FluidSurface {
+            x,
+            y,
+            height_mag: scale * params.height_scale,
+            width: scale,
+            sim: Some(FluidSim::new(fluid::create(solver, x as usize + 1, y as usize + 1), params.clone(), x as usize + 1, y as usize + 1)),
+            params,
+            kind: solver,
+            thread: None,
+            front: Frame::new(cells),
+            solid: vec![false; cells],
+            geometry,
+            material,
+            bounds,
+            time: 0.0,
+            pending: 0.0,
+            has_obstacles: false,
+        }
+    }
+
+    pub fn params(&self) -> &FluidParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: FluidParams) {
+        self.height_mag = self.width * params.height_scale;
+        self.send(Command::Params(params.clone()));
+        self.params = params;
+    }
+
+    pub fn solver_kind(&self) -> SolverKind {
+        self.kind
+    }
+
+    fn send(&mut self, command: Command) {
+        match (self.thread.as_mut(), self.sim.as_mut()) {
+            (Some(thread), _) => thread.send(command),
+            (None, Some(sim)) => {
+                sim.apply(command, &mut self.front);
+            }
+            (None, None) => {}
+        }
+    }
+
+    // moves the simulation onto or off the worker when `threaded` changes
+    fn place_simulation(&mut self) {
+        if self.params.threaded && self.thread.is_none() {
+            if let Some(sim) = self.sim.take() {
+                self.thread = Some(SolverThread::spawn(sim));
+            }
+        } else if !self.params.threaded {
+            if let Some(mut thread) = self.thread.take() {
+                thread.wait(&mut self.front, 0);
+                self.sim = Some(thread.stop());
+            }
+        }
+    }
+
+    fn cell_size(&self) -> f32 {
+        self.width / self.x as f32
+    }
+
+    // grid position of a local-space point, None outside the surface
+    fn cell_at(&self, x: f32, z: f32) -> Option<(f32, f32)> {
+        let dx = self.cell_size();
+        let cx = (x + self.x as f32 * dx / 2.0) / dx;
+        let cz = (z + self.y as f32 * dx / 2.0) / dx;
+        if cx < 0.0 || cz < 0.0 || cx > self.x as f32 || cz > self.y as f32 {
+            return None;
+        }
+        Some((cx, cz))
+    }
+
+    // marks every cell along a local-space segment, stepping half a cell at a time
+    fn rasterize_segment(&self, mask: &mut [bool], a: cgmath::Vector3<f32>, b: cgmath::Vector3<f32>) {
+        let steps = ((b - a).magnitude() / (self.cell_size() * 0.5)).ceil().max(1.0) as u32;
+        for s in 0..steps + 1 {
+            let p = a.lerp(b, s as f32 / steps as f32);
+            if let Some((cx, cz)) = self.cell_at(p.x, p.z) {
+                mask[cz.round() as usize * (self.x as usize + 1) + cx.round() as usize] = true;
+            }
+        }
+    }
+
+    // fills the inside of closed local-space outlines row by row, pairing up the crossings
+    // of each grid row in x order (even-odd); the outline itself is marked as well so thin
+    // or open cross-sections still block
+    fn rasterize_outline(&self, mask: &mut [bool], segments: &[(cgmath::Vector3<f32>, cgmath::Vector3<f32>)]) {
+        let dx = self.cell_size();
+        let half = cgmath::Vector2::new(self.x as f32 * dx / 2.0, self.y as f32 * dx / 2.0);
+        let grid = |p: cgmath::Vector3<f32>| cgmath::Vector2::new((p.x + half.x) / dx, (p.z + half.y) / dx);
+        let mut crossings = Vec::new();
+        for z in 0..self.y as usize + 1 {
+            let row = z as f32;
+            crossings.clear();
+            for &(a, b) in segments {
+                let (a, b) = (grid(a), grid(b));
+                if (a.y <= row) != (b.y <= row) {
+                    crossings.push(a.x + (b.x - a.x) * (row - a.y) / (b.y - a.y));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            for pair in crossings.chunks(2).filter(|pair| pair.len() == 2) {
+                let x0 = pair[0].ceil().max(0.0) as usize;
+                let x1 = pair[1].floor().min(self.x as f32);
+                if x1 < 0.0 {
+                    continue;
+                }
+                for x in x0..x1 as usize + 1 {
+                    mask[z * (self.x as usize + 1) + x] = true;
+                }
+            }
+        }
+        for &(a, b) in segments {
+            self.rasterize_segment(mask, a, b);
+        }
+    }
+
+    // fills the cells under a local-space box that reaches the surface
+    fn rasterize_bounds(&self, mask: &mut [bool], bounds: &mesh::Aabb) {
+        let surface = match self.bounds {
+            Some(surface) => surface,
+            None => return,
+        };
+        if bounds.max.y < surface.min.y || bounds.min.y > surface.max.y {
+            return;
+        }
+        let dx = self.cell_size();
+        let half = cgmath::Vector3::new(self.x as f32 * dx / 2.0, 0.0, self.y as f32 * dx / 2.0);
+        let x0 = (((bounds.min.x + half.x) / dx).floor().max(0.0)) as usize;
+        let z0 = (((bounds.min.z + half.z) / dx).floor().max(0.0)) as usize;
+        let x1 = (((bounds.max.x + half.x) / dx).ceil().min(self.x as f32)) as usize;
+        let z1 = (((bounds.max.z + half.z) / dx).ceil().min(self.y as f32)) as usize;
+        for z in z0..z1 + 1 {
+            for x in x0..x1 + 1 {
+                if x <= self.x as usize && z <= self.y as usize {
+                    mask[z * (self.x as usize + 1) + x] = true;
+                }
+            }
+        }
+    }
+
+    // local-space surface height at the nearest vertex
+    fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.cell_at(x, z).map(|(cx, cz)| {
+            let i = cz.round() as usize * (self.x as usize + 1) + cx.round() as usize;
+            self.geometry.vertices[i].position[1]
+        })
+    }
+
+    // writes the front frame into the mesh, blended by the leftover time
+    fn remesh(&mut self) {
+        let step = 1.0 / self.params.substep_rate.max(1.0);
+        let alpha = if self.params.interpolate { (self.pending / step).max(0.0).min(1.0) as f32 } else { 1.0 };
+        let height_mag = self.height_mag;
+        // gradients are per cell; this turns them into world-space slopes
+        let slope = height_mag / (self.width / self.x as f32);
+        let front = &self.front;
+        self.geometry.vertices
+            .par_iter_mut()
+            .zip(front.previous.par_iter().zip(front.heights.par_iter()))
+            .zip(front.previous_gradients.par_iter().zip(front.gradients.par_iter()))
+            .for_each(|((item, (&p, &c)), (gp, gc))| {
+                item.position[1] = (p + (c - p) * alpha) * height_mag;
+                let gx = (gp[0] + (gc[0] - gp[0]) * alpha) * slope;
+                let gz = (gp[1] + (gc[1] - gp[1]) * alpha) * slope;
+                item.normal = cgmath::Vector3::new(-gx, -1.0, -gz).normalize().into();
+            });
+
+        let (mut min_y, mut max_y) = (std::f32::MAX, std::f32::MIN);
+        for item in &self.geometry.vertices {
+            min_y = min_y.min(item.position[1]);
+            max_y = max_y.max(item.position[1]);
+        }
+        if let Some(ref mut bounds) = self.bounds {
+            bounds.min.y = min_y;
+            bounds.max.y = max_y;
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.place_simulation();
+        let step = 1.0 / self.params.substep_rate.max(1.0);
+        let max_steps = self.params.max_substeps.max(1);
+        self.pending += dt;
+        let steps = ((self.pending / step).floor() as u32).min(max_steps);
+        if steps == max_steps {
+            self.pending = self.pending.min(step * (steps + 1) as f64);
+        }
+        if steps > 0 {
+            // a worker that can't keep up slows the frame rate rather than lagging further behind
+            if let Some(ref mut thread) = self.thread {
+                thread.wait(&mut self.front, MAX_IN_FLIGHT - 1);
+            }
+            let time = self.time;
+            self.pending -= step * steps as f64;
+            self.time += step * steps as f64;
+            self.send(Command::Step { steps, dt: step as f32, time });
+        }
+        let received = match self.thread {
+            Some(ref mut thread) => thread.receive(&mut self.front),
+            None => steps > 0,
+        };
+        if received || self.params.interpolate {
+            self.remesh();
+        }
+    }
+
+    // waits for the worker so offline renders see every step
+    pub fn sync(&mut self) {
+        if let Some(ref mut thread) = self.thread {
+            thread.wait(&mut self.front, 0);
+        }
+        self.remesh();
+    }
+
+    pub fn draw(&mut self, ctx: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder, model: cgmath::Matrix4<f32>) {
+        if self.material.see_through() {
+            ctx.request_scene_copy();
+        }
+        if let Some(bounds) = self.local_bounds().map(|b| b.transform(model)) {
+            if !ctx.is_visible(&mesh::BoundingSphere::from_aabb(&bounds), &bounds) {
+                return;
+            }
+        }
+        self.material.draw(
+            ctx,
+            encoder,
+            &self.geometry,
+            model,
+        );
+    }
+
+    // bounds of the surface as last drawn, before the world matrix is applied
+    pub fn local_bounds(&self) -> Option<mesh::Aabb> {
+        self.bounds
+    }
+
+    pub fn material_params(&self) -> Option<material::MaterialParams> {
+        self.material.params()
+    }
+
+    pub fn set_material_params(&mut self, params: material::MaterialParams) {
+        self.material.set_params(params);
+    }
+
+    pub fn edit_material_params(&mut self, edit: &Fn(&mut material::MaterialParams)) {
+        if let Some(mut params) = self.material.params() {
+            edit(&mut params);
+            self.material.set_params(params);
+        }
+    }
+
+    pub fn water_params(&self) -> material::WaterParams {
+        self.material.water_params()
+    }
+
+    pub fn set_water_params(&mut self, params: material::WaterParams) {
+        self.material.set_water_params(params);
+    }
+
+    // distance along the world-space ray to the surface: intersect the rest
+    // plane, then refine twice against the displaced surface
+    pub fn raycast(&self, model: cgmath::Matrix4<f32>, ray: &mesh::Ray) -> Option<f32> {
+        let inverse = model.invert()?;
+        let local = ray.transform(inverse);
+        let mut t = local.intersect_horizontal(0.0)?;
+        for _ in 0..2 {
+            let p = local.at(t);
+            let height = self.height_at(p.x, p.z)?;
+            t = local.intersect_horizontal(height).unwrap_or(t);
+        }
+        let p = local.at(t);
+        self.cell_at(p.x, p.z).map(|_| t)
+    }
+
+    // pushes the surface around a world-space point
+    pub fn disturb(&mut self, model: cgmath::Matrix4<f32>, point: cgmath::Vector3<f32>, disturbance: &Disturbance) {
+        let inverse = match model.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let p = (inverse * point.extend(1.0)).truncate();
+        if let Some((cx, cz)) = self.cell_at(p.x, p.z) {
+            let scale = model.x.truncate().magnitude();
+            let radius = disturbance.radius / scale / self.cell_size();
+            self.send(Command::Disturb { x: cx, z: cz, radius, amount: disturbance.strength });
+        }
+    }
+
+    // the rest plane, unless the reflection has been turned off
+    pub fn reflection_plane(&self, model: cgmath::Matrix4<f32>) -> Option<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)> {
+        if self.material.water_params().reflection > 0.0 {
+            Some(rest_plane(model))
+        } else {
+            None
+        }
+    }
+
+    // holds the cells under world-space obstacles flat
+    pub fn set_obstacles(&mut self, model: cgmath::Matrix4<f32>, obstacles: &[Obstacle]) {
+        let inverse = match model.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let local = |p: cgmath::Vector3<f32>| (inverse * p.extend(1.0)).truncate();
+        let mut mask = vec![false; self.solid.len()];
+        for obstacle in obstacles {
+            match *obstacle {
+                Obstacle::Segments(ref segments) => {
+                    let segments: Vec<_> = segments.iter().map(|&(a, b)| (local(a), local(b))).collect();
+                    self.rasterize_outline(&mut mask, &segments);
+                }
+                Obstacle::Bounds(ref bounds) => {
+                    if let Some(bounds) = mesh::Aabb::from_points(bounds.corners().iter().map(|&c| local(c).into())) {
+                        self.rasterize_bounds(&mut mask, &bounds);
+                    }
+                }
+            }
+        }
+
+        if self.has_obstacles && mask == self.solid {
+            return;
+        }
+
+        // moving obstacles push water up in front of them and leave a trough behind
+        if self.has_obstacles {
+            let width = self.x as usize + 1;
+            for (i, (&now, &before)) in mask.iter().zip(self.solid.iter()).enumerate() {
+                if now != before {
+                    let amount = if now { WAKE_STRENGTH } else { -WAKE_STRENGTH };
+                    self.send(Command::Disturb { x: (i % width) as f32, z: (i / width) as f32, radius: 1.0, amount });
+                }
+            }
+        }
+        self.send(Command::Solid(mask.clone()));
+        self.solid = mask;
+        self.has_obstacles = true;
+    }
+
+    // world-space (point, up normal) of the surface above or below a point, at
+    // the nearest vertex as drawn; its normal is stored pointing down
+    pub fn surface(&self, model: cgmath::Matrix4<f32>, point: cgmath::Vector3<f32>) -> Option<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)> {
+        let p = (model.invert()? * point.extend(1.0)).truncate();
+        let (cx, cz) = self.cell_at(p.x, p.z)?;
+        let vertex = &self.geometry.vertices[cz.round() as usize * (self.x as usize + 1) + cx.round() as usize];
+        let n = vertex.normal;
+        let position = model * cgmath::Vector4::new(p.x, vertex.position[1], p.z, 1.0);
+        let normal = (model * cgmath::Vector4::new(n[0], -n[1], n[2], 0.0)).truncate();
+        Some((position.truncate(), normal.normalize()))
+    }
+
+    // draw any random initial state from the scene's seeded stream
+    pub fn randomize(&mut self, rng: &mut RngCore) {
+        self.send(Command::Randomize(rng.gen()));
+    }
+
+    // how to recreate the surface when the scene is saved
+    pub fn describe(&self) -> scene::EntityKind {
+        scene::EntityKind::Fluid {
+            width: self.x,
+            height: self.y,
+            scale: self.width,
+            solver: self.kind,
+        }
+    }
+}
+
+fn test_data() -> (Vec<Vertex>, Vec<u32>) {
+    let vertex_data = vec![
+        //top (0, 0, 1)
+        Vertex::new([-1.0, -1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([1.0, -1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([1.0, 1.0, 1.0], [0.0, 0.0, 1.0]),
+        Vertex::new([-1.0, 1.0, 1.0], [0.0, 0.0, 1.0]),
+        //bottom (0.0, 0.0, -1.0)
+        Vertex::new([1.0, 1.0, -1.0], [0.0, 0.0, -1.0]),
+        Vertex::new([-1.0, 1.0, -1.0], [0.0, 0.0, -1.0]),
+        Vertex::new([-1.0, -1.0, -1.0], [0.0, 0.0, -1.0]),
+        Vertex::new([1.0, -1.0, -1.0], [0.0, 0.0, -1.0]),
+        //right (1.0, 0.0, 0.0)
+        Vertex::new([1.0, -1.0, -1.0], [1.0, 0.0, 0.0]),
+        Vertex::new([1.0, 1.0, -1.0], [1.0, 0.0, 0.0]),
+        Vertex::new([1.0, 1.0, 1.0], [1.0, 0.0, 0.0]),
+        Vertex::new([1.0, -1.0, 1.0], [1.0, 0.0, 0.0]),
+        //left (-1.0, 0.0, 0.0)
+        Vertex::new([-1.0, 1.0, 1.0], [-1.0, 0.0, 0.0]),
+        Vertex::new([-1.0, -1.0, 1.0], [-1.0, 0.0, 0.0]),
+        Vertex::new([-1.0, -1.0, -1.0], [-1.0, 0.0, 0.0]),
+        Vertex::new([-1.0, 1.0, -1.0], [-1.0, 0.0, 0.0]),
+        //front (0.0, 1.0, 0.0)
+        Vertex::new([-1.0, 1.0, -1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([1.0, 1.0, -1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([1.0, 1.0, 1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([-1.0, 1.0, 1.0], [0.0, 1.0, 0.0]),
+        //back (0.0, -1.0, 0.0)
+        Vertex::new([1.0, -1.0, 1.0], [0.0, -1.0, 0.0]),
+        Vertex::new([-1.0, -1.0, 1.0], [0.0, -1.0, 0.0]),
+        Vertex::new([-1.0, -1.0, -1.0], [0.0, -1.0, 0.0]),
+        Vertex::new([1.0, -1.0, -1.0], [0.0, -1.0, 0.0]),
+    ];
+
+    let index_data = vec![
+        0, 1, 2, 2, 3, 0, // top
+        4, 6, 5, 6, 4, 7, // bottom
+        8, 9, 10, 10, 11, 8, // right
+        12, 14, 13, 14, 12, 15, // left
+        16, 18, 17, 18, 16, 19, // front
+        20, 21, 22, 22, 23, 20, // back
+    ];
+
+    (vertex_data, index_data)
+}
\ No newline at end of file
@@ -0,0 +1,110 @@
+use std;
+use super::{splash, Boundary, FluidParams, HeightfieldSolver, SolverKind};
+
+// largest squared courant number the explicit scheme stays stable at
+const MAX_C2: f32 = 0.5;
+const MAX_VISCOSITY: f32 = 0.2;
+
+// damped wave equation, three height buffers and no velocity
+pub struct WaveSolver {
+    width: usize,
+    height: usize,
+    current: Vec<f32>,
+    previous: Vec<f32>,
+    next: Vec<f32>,
+    solid: Vec<bool>,
+}
+
+impl WaveSolver {
+    pub fn new(width: usize, height: usize) -> Self {
+        WaveSolver {
+            width,
+            height,
+            current: vec![0.0; width * height],
+            previous: vec![0.0; width * height],
+            next: vec![0.0; width * height],
+            solid: vec![false; width * height],
+        }
+    }
+
+    fn neighbor(&self, x: usize, z: usize, dx: isize, dz: isize, periodic: bool) -> usize {
+        let (w, h) = (self.width as isize, self.height as isize);
+        let (nx, nz) = (x as isize + dx, z as isize + dz);
+        let (nx, nz) = if periodic {
+            ((nx + w) % w, (nz + h) % h)
+        } else {
+            // mirrored edges reflect without inverting the wave
+            (nx.max(0).min(w - 1), nz.max(0).min(h - 1))
+        };
+        (nz * w + nx) as usize
+    }
+
+    fn laplacian(&self, buffer: &[f32], x: usize, z: usize, periodic: bool) -> f32 {
+        buffer[self.neighbor(x, z, -1, 0, periodic)] + buffer[self.neighbor(x, z, 1, 0, periodic)]
+            + buffer[self.neighbor(x, z, 0, -1, periodic)] + buffer[self.neighbor(x, z, 0, 1, periodic)]
+            - 4.0 * buffer[z * self.width + x]
+    }
+
+    // first-order Mur condition: outgoing waves leave instead of bouncing back
+    fn absorb_edges(&mut self, c: f32) {
+        let (w, h) = (self.width, self.height);
+        let k = (c - 1.0) / (c + 1.0);
+        let mur = |edge: usize, inner: usize, solver: &mut WaveSolver| {
+            if !solver.solid[edge] {
+                solver.next[edge] = solver.current[inner] + k * (solver.next[inner] - solver.current[edge]);
+            }
+        };
+        for x in 0..w {
+            mur(x, w + x, self);
+            mur((h - 1) * w + x, (h - 2) * w + x, self);
+        }
+        for z in 0..h {
+            mur(z * w, z * w + 1, self);
+            mur(z * w + w - 1, z * w + w - 2, self);
+        }
+    }
+}
+
+impl HeightfieldSolver for WaveSolver {
+    fn kind(&self) -> SolverKind {
+        SolverKind::Wave
+    }
+
+    fn step(&mut self, dt: f32, params: &FluidParams) {
+        let (w, h) = (self.width, self.height);
+        let c2 = (params.wave_speed.max(0.0) * dt).powi(2).min(MAX_C2);
+        let viscosity = (params.viscosity.max(0.0) * dt).min(MAX_VISCOSITY);
+        let keep = params.damping.max(0.0).min(1.0).powf(dt);
+        let periodic = params.boundary == Boundary::Periodic;
+        for z in 0..h {
+            for x in 0..w {
+                let i = z * w + x;
+                if self.solid[i] {
+                    self.next[i] = 0.0;
+                    continue;
+                }
+                let lap = self.laplacian(&self.current, x, z, periodic);
+                let lap_previous = self.laplacian(&self.previous, x, z, periodic);
+                let c = self.current[i];
+                self.next[i] = (2.0 * c - self.previous[i] + c2 * lap + viscosity * (lap - lap_previous)) * keep;
+            }
+        }
+        if params.boundary == Boundary::Absorbing && w > 1 && h > 1 {
+            self.absorb_edges(c2.sqrt());
+        }
+        std::mem::swap(&mut self.previous, &mut self.current);
+        std::mem::swap(&mut self.current, &mut self.next);
+    }
+
+    fn heights(&self) -> &[f32] {
+        &self.current
+    }
+
+    fn disturb(&mut self, x: f32, z: f32, radius: f32, amount: f32) {
+        splash(&mut self.current, self.width, self.height, x, z, radius, amount);
+    }
+
+    fn set_solid(&mut self, solid: &[bool]) {
+        self.solid.copy_from_slice(solid);
+    }
+}
@@ -0,0 +1,214 @@
+use std;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use random::Random;
+use super::{gradients, Emitter, FluidParams, HeightfieldSolver};
+
+// heights before and after the latest step with their slopes in cell units
+pub struct Frame {
+    pub previous: Vec<f32>,
+    pub heights: Vec<f32>,
+    pub previous_gradients: Vec<[f32; 2]>,
+    pub gradients: Vec<[f32; 2]>,
+    // step commands processed when it was filled
+    pub sequence: u64,
+}
+
+impl Frame {
+    pub fn new(cells: usize) -> Self {
+        Frame {
+            previous: vec![0.0; cells],
+            heights: vec![0.0; cells],
+            previous_gradients: vec![[0.0; 2]; cells],
+            gradients: vec![[0.0; 2]; cells],
+            sequence: 0,
+        }
+    }
+}
+
+pub enum Command {
+    // `steps` solver steps of `dt`, the first starting at `time`
+    Step { steps: u32, dt: f32, time: f64 },
+    Disturb { x: f32, z: f32, radius: f32, amount: f32 },
+    Solid(Vec<bool>),
+    Params(FluidParams),
+    Randomize(u64),
+    Stop,
+}
+
+// a solver with its sources, driven the same way on either thread
+pub struct FluidSim {
+    solver: Box<HeightfieldSolver>,
+    emitter: Emitter,
+    params: FluidParams,
+    width: usize,
+    height: usize,
+    sequence: u64,
+}
+
+impl FluidSim {
+    pub fn new(solver: Box<HeightfieldSolver>, params: FluidParams, width: usize, height: usize) -> Self {
+        FluidSim {
+            solver,
+            emitter: Emitter::new(Random::new(0).stream("rain")),
+            params,
+            width,
+            height,
+            sequence: 0,
+        }
+    }
+
+    pub fn solver(&self) -> &HeightfieldSolver {
+        &*self.solver
+    }
+
+    // true when `frame` was refilled
+    pub fn apply(&mut self, command: Command, frame: &mut Frame) -> bool {
+        match command {
+            Command::Step { steps, dt, time } => {
+                for i in 0..steps {
+                    if i + 1 == steps {
+                        frame.previous.copy_from_slice(self.solver.heights());
+                    }
+                    let t = time + (i + 1) as f64 * dt as f64;
+                    self.emitter.emit(&self.params.sources, &mut *self.solver, self.width, self.height, t as f32, dt);
+                    self.solver.step(dt, &self.params);
+                }
+                frame.heights.copy_from_slice(self.solver.heights());
+                gradients(&frame.previous, self.width, self.height, 1.0, &mut frame.previous_gradients);
+                gradients(&frame.heights, self.width, self.height, 1.0, &mut frame.gradients);
+                self.sequence += 1;
+                frame.sequence = self.sequence;
+                true
+            }
+            Command::Disturb { x, z, radius, amount } => {
+                self.solver.disturb(x, z, radius, amount);
+                false
+            }
+            Command::Solid(mask) => {
+                self.solver.set_solid(&mask);
+                false
+            }
+            Command::Params(params) => {
+                self.params = params;
+                false
+            }
+            Command::Randomize(seed) => {
+                let random = Random::new(seed);
+                self.solver.randomize(&mut random.stream("solver"));
+                self.emitter = Emitter::new(random.stream("rain"));
+                false
+            }
+            Command::Stop => false,
+        }
+    }
+}
+
+struct Shared {
+    ready: Mutex<Option<Frame>>,
+    published: Condvar,
+}
+
+// runs a FluidSim on its own thread; finished frames wait in a slot for the
+// caller, and the frames it is done with come back for reuse
+pub struct SolverThread {
+    commands: Sender<Command>,
+    recycle: Sender<Frame>,
+    shared: Arc<Shared>,
+    handle: JoinHandle<FluidSim>,
+    sent: u64,
+    received: u64,
+}
+
+impl SolverThread {
+    pub fn spawn(mut sim: FluidSim) -> Self {
+        let (commands, inbox) = mpsc::channel();
+        let (recycle, spares) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            ready: Mutex::new(None),
+            published: Condvar::new(),
+        });
+        let sent = sim.sequence;
+        let worker = shared.clone();
+        let handle = thread::Builder::new()
+            .name("fluid".to_string())
+            .spawn(move || {
+                let cells = sim.width * sim.height;
+                let mut back = Frame::new(cells);
+                // ends on Stop, or when the owner is dropped
+                for command in inbox.iter() {
+                    if let Command::Stop = command {
+                        break;
+                    }
+                    if sim.apply(command, &mut back) {
+                        let mut ready = worker.ready.lock().unwrap();
+                        // an unread frame is overwritten next time
+                        let unread = std::mem::replace(&mut *ready, Some(back));
+                        back = unread
+                            .or_else(|| spares.try_recv().ok())
+                            .unwrap_or_else(|| Frame::new(cells));
+                        worker.published.notify_all();
+                    }
+                }
+                sim
+            })
+            .unwrap();
+        SolverThread {
+            commands,
+            recycle,
+            shared,
+            handle,
+            sent,
+            received: sent,
+        }
+    }
+
+    pub fn send(&mut self, command: Command) {
+        if let Command::Step { .. } = command {
+            self.sent += 1;
+        }
+        let _ = self.commands.send(command);
+    }
+
+    // step commands not yet seen in a received frame
+    pub fn in_flight(&self) -> u64 {
+        self.sent - self.received
+    }
+
+    // swaps in the newest published frame, if any
+    pub fn receive(&mut self, front: &mut Frame) -> bool {
+        let frame = self.shared.ready.lock().unwrap().take();
+        match frame {
+            Some(frame) => {
+                self.swap_in(front, frame);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // blocks until at most `behind` step commands are in flight
+    pub fn wait(&mut self, front: &mut Frame, behind: u64) {
+        let shared = self.shared.clone();
+        let mut ready = shared.ready.lock().unwrap();
+        while self.in_flight() > behind {
+            match ready.take() {
+                Some(frame) => self.swap_in(front, frame),
+                None => ready = shared.published.wait(ready).unwrap(),
+            }
+        }
+    }
+
+    // finishes the queued commands and hands the simulation back
+    pub fn stop(self) -> FluidSim {
+        let _ = self.commands.send(Command::Stop);
+        self.handle.join().unwrap()
+    }
+
+    fn swap_in(&mut self, front: &mut Frame, frame: Frame) {
+        self.received = frame.sequence;
+        let old = std::mem::replace(front, frame);
+        let _ = self.recycle.send(old);
+    }
+}
@@ -0,0 +1,63 @@
+use cgmath::{self, prelude::*};
+use mesh;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: cgmath::Vector3<f32>,
+    pub d: f32,
+}
+
+impl Plane {
+    fn from_vector(v: cgmath::Vector4<f32>) -> Self {
+        let normal = v.truncate();
+        let len = normal.magnitude();
+        Plane {
+            normal: normal / len,
+            d: v.w / len,
+        }
+    }
+
+    pub fn distance(&self, p: cgmath::Vector3<f32>) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Gribb/Hartmann plane extraction from a projection * view matrix
+    pub fn from_matrix(m: cgmath::Matrix4<f32>) -> Self {
+        let r0 = m.row(0);
+        let r1 = m.row(1);
+        let r2 = m.row(2);
+        let r3 = m.row(3);
+        Frustum {
+            planes: [
+                Plane::from_vector(r3 + r0),
+                Plane::from_vector(r3 - r0),
+                Plane::from_vector(r3 + r1),
+                Plane::from_vector(r3 - r1),
+                Plane::from_vector(r3 + r2),
+                Plane::from_vector(r3 - r2),
+            ],
+        }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &mesh::BoundingSphere) -> bool {
+        self.planes.iter().all(|p| p.distance(sphere.center) >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, bounds: &mesh::Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            let v = cgmath::Vector3::new(
+                if p.normal.x >= 0.0 { bounds.max.x } else { bounds.min.x },
+                if p.normal.y >= 0.0 { bounds.max.y } else { bounds.min.y },
+                if p.normal.z >= 0.0 { bounds.max.z } else { bounds.min.z },
+            );
+            p.distance(v) >= 0.0
+        })
+    }
+}
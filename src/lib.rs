@@ -0,0 +1,43 @@
+extern crate piston_window;
+extern crate camera_controllers;
+extern crate cgmath;
+#[macro_use]
+extern crate gfx;
+extern crate shader_version;
+#[macro_use]
+extern crate gfx_macros;
+#[macro_use]
+extern crate conrod;
+extern crate gfx_device_gl;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate ron;
+extern crate rand;
+extern crate serde_json;
+extern crate png;
+extern crate rayon;
+
+pub mod camera;
+pub mod clock;
+pub mod camera_path;
+pub mod world;
+pub mod render;
+pub mod entity;
+pub mod material;
+pub mod mesh;
+pub mod transform;
+pub mod scene_graph;
+pub mod scene;
+pub mod component;
+pub mod animation;
+pub mod frustum;
+pub mod fluid;
+pub mod random;
+pub mod params;
+pub mod batch;
+pub mod export;
+pub mod sketch;
+pub mod options;
+mod ui_handler;
+pub mod app;
@@ -3,7 +3,7 @@ extern crate image;
 use std;
 use gfx::{self, traits::*, pso};
 use render;
-use mesh::Geometry;
+use mesh::{self, Geometry};
 use piston_window;
 use cgmath::{self, prelude::*};
 use shader_version::Shaders;
@@ -26,6 +26,12 @@ impl Vertex {
     }
 }
 
+impl mesh::Position for Vertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
 #[derive(VertexData, Clone, Copy)]
 pub struct VertexTexture {
     pub position: [f32; 3],
@@ -43,6 +49,12 @@ impl VertexTexture {
     }
 }
 
+impl mesh::Position for VertexTexture {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
 gfx_pipeline!( pipe {
     vbuf: gfx::VertexBuffer<Vertex> = (),
     u_model_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_model_view_proj",
@@ -58,6 +70,21 @@ pub struct DirectionalLight {
 }
 
 impl DirectionalLight {
+    pub fn new(direction: [f32; 3], color: [f32; 3]) -> Self {
+        DirectionalLight {
+            direction: [direction[0], direction[1], direction[2], 0.0],
+            color: [color[0], color[1], color[2], 1.0],
+        }
+    }
+
+    pub fn direction(&self) -> [f32; 3] {
+        [self.direction[0], self.direction[1], self.direction[2]]
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        [self.color[0], self.color[1], self.color[2]]
+    }
+
     pub fn transform(&self, mat: cgmath::Matrix3<f32>) -> Self {
         DirectionalLight {
             direction: (mat * cgmath::Vector4::from(self.direction).truncate()).extend(0.0).into(),
@@ -74,6 +101,30 @@ pub struct PointLight {
 }
 
 impl PointLight {
+    pub fn new(position: [f32; 3], color: [f32; 3], distance: f32, decay: f32) -> Self {
+        PointLight {
+            position: [position[0], position[1], position[2], 1.0],
+            color: [color[0], color[1], color[2], 1.0],
+            distance_decay: [distance, decay, 0.0, 0.0],
+        }
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        [self.position[0], self.position[1], self.position[2]]
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        [self.color[0], self.color[1], self.color[2]]
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance_decay[0]
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.distance_decay[1]
+    }
+
     pub fn transform(&self, mat: cgmath::Matrix4<f32>) -> Self {
         PointLight {
             position: (mat * cgmath::Vector4::from(self.position)).into(),
@@ -130,11 +181,105 @@ gfx_pipeline!( pipe_pbr_tex {
     p_num: gfx::Global<i32> = "numPointLights",
     s_num: gfx::Global<i32> = "numSpotLights",
     t_color: gfx::TextureSampler<[f32; 4]> = "t_color",
+    screen_size: gfx::Global<[f32; 2]> = "screen_size",
+    has_reflection: gfx::Global<i32> = "has_reflection",
+    reflection_normal: gfx::Global<[f32; 3]> = "reflection_normal",
+    reflection_distortion: gfx::Global<f32> = "reflection_distortion",
+    t_reflection: gfx::TextureSampler<[f32; 4]> = "t_reflection",
+    out_color: gfx::RenderTarget<::gfx::format::Srgba8> = "f_color",
+    out_depth: gfx::DepthTarget<::gfx::format::DepthStencil> =
+    gfx::preset::depth::LESS_EQUAL_WRITE,
+});
+
+gfx_pipeline!( pipe_water {
+    vbuf: gfx::VertexBuffer<VertexTexture> = (),
+    model_matrix: gfx::Global<[[f32; 4]; 4]> = "model_matrix",
+    model_view_matrix: gfx::Global<[[f32; 4]; 4]> = "model_view_matrix",
+    projection_matrix: gfx::Global<[[f32; 4]; 4]> = "projection_matrix",
+    normal_matrix: gfx::Global<[[f32; 3]; 3]> = "normal_matrix",
+    inverse_view: gfx::Global<[[f32; 4]; 4]> = "inverse_view",
+    inverse_projection: gfx::Global<[[f32; 4]; 4]> = "inverse_projection",
+    screen_size: gfx::Global<[f32; 2]> = "screen_size",
+    time: gfx::Global<f32> = "time",
+    scatter_color: gfx::Global<[f32; 3]> = "scatter_color",
+    absorption: gfx::Global<[f32; 3]> = "absorption",
+    sky_color: gfx::Global<[f32; 3]> = "sky_color",
+    horizon_color: gfx::Global<[f32; 3]> = "horizon_color",
+    refraction: gfx::Global<f32> = "refraction",
+    roughness: gfx::Global<f32> = "roughness",
+    foam: gfx::Global<f32> = "foam",
+    foam_slope: gfx::Global<f32> = "foam_slope",
+    shore_foam: gfx::Global<f32> = "shore_foam",
+    caustics: gfx::Global<f32> = "caustics",
+    sun_direction: gfx::Global<[f32; 3]> = "sun_direction",
+    sun_color: gfx::Global<[f32; 3]> = "sun_color",
+    reflection: gfx::Global<f32> = "reflection",
+    reflection_distortion: gfx::Global<f32> = "reflection_distortion",
+    has_scene: gfx::Global<i32> = "has_scene",
+    has_reflection: gfx::Global<i32> = "has_reflection",
+    t_scene: gfx::TextureSampler<[f32; 4]> = "t_scene",
+    t_depth: gfx::TextureSampler<f32> = "t_depth",
+    t_reflection: gfx::TextureSampler<[f32; 4]> = "t_reflection",
     out_color: gfx::RenderTarget<::gfx::format::Srgba8> = "f_color",
     out_depth: gfx::DepthTarget<::gfx::format::DepthStencil> =
     gfx::preset::depth::LESS_EQUAL_WRITE,
 });
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MaterialParams {
+    pub metallic: f32,
+    pub roughness: f32,
+    pub albedo: [f32; 3],
+    pub emissive: [f32; 3],
+    pub opacity: f32,
+}
+
+// distances are in world units
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaterParams {
+    // what deep water fades to
+    pub scatter_color: [f32; 3],
+    // per unit of depth for each channel
+    pub absorption: [f32; 3],
+    // environment reflected at the top and at the horizon
+    pub sky_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    // screen-space offset of the scene behind per unit of normal tilt
+    pub refraction: f32,
+    pub roughness: f32,
+    pub foam: f32,
+    // surface slope where foam starts
+    pub foam_slope: f32,
+    // water shallower than this foams against what's below it
+    pub shore_foam: f32,
+    // brightness of the caustic pattern on what's under the surface, 0 for none
+    pub caustics: f32,
+    // share of the planar reflection over the sky, 0 skips drawing it
+    pub reflection: f32,
+    // screen-space offset of the reflection per unit of normal tilt
+    pub reflection_distortion: f32,
+}
+
+impl Default for WaterParams {
+    fn default() -> Self {
+        WaterParams {
+            scatter_color: [0.02, 0.12, 0.15],
+            absorption: [2.0, 0.6, 0.4],
+            sky_color: [0.35, 0.55, 0.85],
+            horizon_color: [0.75, 0.82, 0.9],
+            refraction: 0.05,
+            roughness: 0.1,
+            foam: 1.0,
+            foam_slope: 0.6,
+            shore_foam: 0.05,
+            caustics: 0.5,
+            reflection: 1.0,
+            reflection_distortion: 0.03,
+        }
+    }
+}
+
 pub trait MaterialTrait<V: Pod> {
     fn draw(&mut self,
             ctx: &mut render::RenderContext,
@@ -142,6 +287,14 @@ pub trait MaterialTrait<V: Pod> {
             geometry: &Geometry<V>,
             model_matrix: cgmath::Matrix4<f32>,
     );
+    fn params(&self) -> Option<MaterialParams> {
+        None
+    }
+    fn set_params(&mut self, params: MaterialParams) {}
+    // whether drawing needs the scene behind the surface, see `RenderContext::request_scene_copy`
+    fn see_through(&self) -> bool {
+        false
+    }
 }
 
 pub struct Material<D, R>
@@ -155,6 +308,7 @@ pub struct Material<D, R>
 pub type MaterialBasic = Material<pipe::Data<gfx_device_gl::Resources>, gfx_device_gl::Resources>;
 pub type MaterialPbr = Material<pipe_pbr::Data<gfx_device_gl::Resources>, gfx_device_gl::Resources>;
 pub type MaterialPbrTex = Material<pipe_pbr_tex::Data<gfx_device_gl::Resources>, gfx_device_gl::Resources>;
+pub type MaterialWater = Material<pipe_water::Data<gfx_device_gl::Resources>, gfx_device_gl::Resources>;
 
 impl MaterialPbr {
     pub fn new(
@@ -226,14 +380,45 @@ impl MaterialTrait<Vertex> for MaterialPbr {
                 .unwrap_or(cgmath::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0));
 
         self.data.vbuf = vbuf.clone();
+        self.data.out_color = ctx.output_color.clone();
+        self.data.out_depth = ctx.output_stencil.clone();
         self.data.model_view_matrix = mv_mat.into();
         self.data.projection_matrix = ctx.projection.into();
         self.data.normal_matrix = n_mat.into();
-        encoder.update_buffer(&self.data.d_lights, &[DIR_LIGHT.transform(n_mat)], 0);
-        encoder.update_buffer(&self.data.p_lights, &[], 0);
-        encoder.update_buffer(&self.data.s_lights, &[], 0);
+        if ctx.has_lights() {
+            let d_lights = ctx.view_directional_lights();
+            let p_lights = ctx.view_point_lights();
+            self.data.d_num = d_lights.len() as i32;
+            self.data.p_num = p_lights.len() as i32;
+            encoder.update_buffer(&self.data.d_lights, &d_lights, 0).unwrap();
+            encoder.update_buffer(&self.data.p_lights, &p_lights, 0).unwrap();
+        } else {
+            self.data.d_num = 1;
+            self.data.p_num = 0;
+            encoder.update_buffer(&self.data.d_lights, &[DIR_LIGHT.transform(n_mat)], 0).unwrap();
+            encoder.update_buffer(&self.data.p_lights, &[], 0).unwrap();
+        }
+        encoder.update_buffer(&self.data.s_lights, &[], 0).unwrap();
         encoder.draw(&slice, &self.pso, &self.data);
     }
+
+    fn params(&self) -> Option<MaterialParams> {
+        Some(MaterialParams {
+            metallic: self.data.metallic,
+            roughness: self.data.roughness,
+            albedo: self.data.albedo,
+            emissive: self.data.emissive,
+            opacity: self.data.opacity,
+        })
+    }
+
+    fn set_params(&mut self, params: MaterialParams) {
+        self.data.metallic = params.metallic;
+        self.data.roughness = params.roughness;
+        self.data.albedo = params.albedo;
+        self.data.emissive = params.emissive;
+        self.data.opacity = params.opacity;
+    }
 }
 
 impl MaterialPbrTex {
@@ -293,10 +478,16 @@ impl MaterialPbrTex {
             d_lights: ctx.factory.create_constant_buffer(4),
             p_lights: ctx.factory.create_constant_buffer(4),
             s_lights: ctx.factory.create_constant_buffer(4),
-            d_num: 0,
-            p_num: 1,
+            d_num: 1,
+            p_num: 0,
             s_num: 0,
             t_color: (texture_view.clone(), ctx.factory.create_sampler(sinfo)),
+            screen_size: [1.0, 1.0],
+            has_reflection: 0,
+            reflection_normal: [0.0, 1.0, 0.0],
+            reflection_distortion: 0.0,
+            // a stand-in until a reflection is drawn
+            t_reflection: (texture_view.clone(), ctx.factory.create_sampler(sinfo)),
             out_color: ctx.output_color.clone(),
             out_depth: ctx.output_stencil.clone(),
         };
@@ -325,14 +516,238 @@ impl MaterialTrait<VertexTexture> for MaterialPbrTex {
                 .unwrap_or(cgmath::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0));
 
         self.data.vbuf = vbuf.clone();
+        self.data.out_color = ctx.output_color.clone();
+        self.data.out_depth = ctx.output_stencil.clone();
         self.data.model_view_matrix = mv_mat.into();
         self.data.projection_matrix = ctx.projection.into();
         self.data.normal_matrix = n_mat.into();
-        encoder.update_buffer(&self.data.d_lights, &[DIR_LIGHT.transform(n_mat)], 0).unwrap();
-        encoder.update_buffer(&self.data.p_lights, &[POINT_LIGHT.transform(mv_mat)], 0).unwrap();
+        if ctx.has_lights() {
+            let d_lights = ctx.view_directional_lights();
+            let p_lights = ctx.view_point_lights();
+            self.data.d_num = d_lights.len() as i32;
+            self.data.p_num = p_lights.len() as i32;
+            encoder.update_buffer(&self.data.d_lights, &d_lights, 0).unwrap();
+            encoder.update_buffer(&self.data.p_lights, &p_lights, 0).unwrap();
+        } else {
+            self.data.d_num = 1;
+            self.data.p_num = 0;
+            encoder.update_buffer(&self.data.d_lights, &[DIR_LIGHT.transform(n_mat)], 0).unwrap();
+            encoder.update_buffer(&self.data.p_lights, &[], 0).unwrap();
+        }
         encoder.update_buffer(&self.data.s_lights, &[], 0).unwrap();
+        let (width, height) = ctx.output_size();
+        self.data.screen_size = [width as f32, height as f32];
+        self.data.has_reflection = 0;
+        self.data.reflection_distortion = ctx.reflection_distortion;
+        if let Some(reflection) = ctx.reflection() {
+            self.data.t_reflection.0 = reflection.color.clone();
+            self.data.has_reflection = 1;
+            // in view space, like the normals the shader compares it with
+            self.data.reflection_normal = (ctx.view * reflection.normal.extend(0.0)).truncate().normalize().into();
+        }
         encoder.draw(&slice, &self.pso, &self.data);
     }
+
+    fn params(&self) -> Option<MaterialParams> {
+        Some(MaterialParams {
+            metallic: self.data.metallic,
+            roughness: self.data.roughness,
+            albedo: self.data.albedo,
+            emissive: self.data.emissive,
+            opacity: self.data.opacity,
+        })
+    }
+
+    fn set_params(&mut self, params: MaterialParams) {
+        self.data.metallic = params.metallic;
+        self.data.roughness = params.roughness;
+        self.data.albedo = params.albedo;
+        self.data.emissive = params.emissive;
+        self.data.opacity = params.opacity;
+    }
+}
+
+impl MaterialWater {
+    // turns on the scene copy it refracts
+    pub fn new(
+        ctx: &mut render::RenderContext,
+        params: WaterParams,
+        primitive: gfx::Primitive,
+    ) -> Result<Self, gfx::PipelineStateError<String>> {
+        let glsl = piston_window::OpenGL::V3_2.to_glsl();
+        let set = ctx.factory.create_shader_set(
+            Shaders::new()
+                .set(GLSL::V1_50, include_str!("../assets/water_150_vert.glsl"))
+                .get(glsl).unwrap().as_bytes(),
+            Shaders::new()
+                .set(GLSL::V1_50, include_str!("../assets/water_150_frag.glsl"))
+                .get(glsl).unwrap().as_bytes(),
+        )?;
+        let pso = ctx.factory.create_pipeline_state(
+            &set,
+            primitive,
+            gfx::state::Rasterizer::new_fill(),
+            pipe_water::new(),
+        )?;
+        // the first frame has no earlier draw to ask for it
+        ctx.request_scene_copy();
+
+        // stand-ins until the first scene copy is drawn
+        let kind = gfx::texture::Kind::D2(1, 1, gfx::texture::AaMode::Single);
+        let (_, scene) = ctx.factory
+            .create_texture_immutable_u8::<gfx::format::Srgba8>(kind, gfx::texture::Mipmap::Provided, &[&[0, 0, 0, 255]])
+            .unwrap();
+        let (_, depth) = ctx.factory
+            .create_texture_immutable::<(gfx::format::R32, gfx::format::Float)>(kind, gfx::texture::Mipmap::Provided, &[&[1.0]])
+            .unwrap();
+        let sampler = ctx.factory.create_sampler(gfx::texture::SamplerInfo::new(
+            gfx::texture::FilterMethod::Bilinear,
+            gfx::texture::WrapMode::Clamp));
+
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::from_scale(1.0).into();
+        let data = pipe_water::Data {
+            vbuf: ctx.factory.create_vertex_buffer(&Vec::<VertexTexture>::new()),
+            model_matrix: identity,
+            model_view_matrix: identity,
+            projection_matrix: identity,
+            normal_matrix: cgmath::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0).into(),
+            inverse_view: identity,
+            inverse_projection: identity,
+            screen_size: [1.0, 1.0],
+            time: 0.0,
+            scatter_color: params.scatter_color,
+            absorption: params.absorption,
+            sky_color: params.sky_color,
+            horizon_color: params.horizon_color,
+            refraction: params.refraction,
+            roughness: params.roughness,
+            foam: params.foam,
+            foam_slope: params.foam_slope,
+            shore_foam: params.shore_foam,
+            caustics: params.caustics,
+            sun_direction: [0.0, 1.0, 0.0],
+            sun_color: [1.0, 1.0, 1.0],
+            reflection: params.reflection,
+            reflection_distortion: params.reflection_distortion,
+            has_scene: 0,
+            has_reflection: 0,
+            t_scene: (scene.clone(), sampler.clone()),
+            t_depth: (depth, sampler.clone()),
+            t_reflection: (scene, sampler),
+            out_color: ctx.output_color.clone(),
+            out_depth: ctx.output_stencil.clone(),
+        };
+        Ok(Material {
+            pso,
+            data,
+        })
+    }
+
+    pub fn water_params(&self) -> WaterParams {
+        WaterParams {
+            scatter_color: self.data.scatter_color,
+            absorption: self.data.absorption,
+            sky_color: self.data.sky_color,
+            horizon_color: self.data.horizon_color,
+            refraction: self.data.refraction,
+            roughness: self.data.roughness,
+            foam: self.data.foam,
+            foam_slope: self.data.foam_slope,
+            shore_foam: self.data.shore_foam,
+            caustics: self.data.caustics,
+            reflection: self.data.reflection,
+            reflection_distortion: self.data.reflection_distortion,
+        }
+    }
+
+    pub fn set_water_params(&mut self, params: WaterParams) {
+        self.data.scatter_color = params.scatter_color;
+        self.data.absorption = params.absorption;
+        self.data.sky_color = params.sky_color;
+        self.data.horizon_color = params.horizon_color;
+        self.data.refraction = params.refraction;
+        self.data.roughness = params.roughness;
+        self.data.foam = params.foam;
+        self.data.foam_slope = params.foam_slope;
+        self.data.shore_foam = params.shore_foam;
+        self.data.caustics = params.caustics;
+        self.data.reflection = params.reflection;
+        self.data.reflection_distortion = params.reflection_distortion;
+    }
+}
+
+impl MaterialTrait<VertexTexture> for MaterialWater {
+    fn see_through(&self) -> bool {
+        true
+    }
+
+    // skipped while the scene behind it is copied
+    fn draw(&mut self,
+            ctx: &mut render::RenderContext,
+            encoder: &mut piston_window::GfxEncoder,
+            geometry: &Geometry<VertexTexture>,
+            model_matrix: cgmath::Matrix4<f32>,
+    ) {
+        if ctx.pass != render::Pass::Main {
+            return;
+        }
+        let (vbuf, slice) = ctx.factory.create_vertex_buffer_with_slice
+        (&geometry.vertices, geometry.indices.as_slice());
+        let mv_mat = ctx.view * model_matrix;
+        let n_mat: cgmath::Matrix3<f32> =
+            cgmath::Matrix3::from_cols(mv_mat.x.truncate(), mv_mat.y.truncate(), mv_mat.z.truncate())
+                .transpose()
+                .invert()
+                .unwrap_or(cgmath::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0));
+        let identity = cgmath::Matrix4::from_scale(1.0);
+        let (width, height) = ctx.output_size();
+        // world-space direction towards the first light
+        let light = ctx.directional_lights.first().cloned().unwrap_or(DIR_LIGHT);
+        let direction = light.direction();
+
+        self.data.vbuf = vbuf;
+        self.data.out_color = ctx.output_color.clone();
+        self.data.out_depth = ctx.output_stencil.clone();
+        self.data.model_matrix = model_matrix.into();
+        self.data.model_view_matrix = mv_mat.into();
+        self.data.projection_matrix = ctx.projection.into();
+        self.data.normal_matrix = n_mat.into();
+        self.data.inverse_view = ctx.view.invert().unwrap_or(identity).into();
+        self.data.inverse_projection = ctx.projection.invert().unwrap_or(identity).into();
+        self.data.screen_size = [width as f32, height as f32];
+        self.data.time = ctx.time;
+        self.data.sun_direction = [-direction[0], -direction[1], -direction[2]];
+        self.data.sun_color = light.color();
+        self.data.has_scene = 0;
+        if let Some(copy) = ctx.scene_copy() {
+            if copy.width == width && copy.height == height {
+                self.data.t_scene.0 = copy.color.clone();
+                self.data.t_depth.0 = copy.depth.clone();
+                self.data.has_scene = 1;
+            }
+        }
+        self.data.has_reflection = 0;
+        if let Some(reflection) = ctx.reflection() {
+            self.data.t_reflection.0 = reflection.color.clone();
+            self.data.has_reflection = 1;
+        }
+        encoder.draw(&slice, &self.pso, &self.data);
+    }
+
+    fn params(&self) -> Option<MaterialParams> {
+        Some(MaterialParams {
+            metallic: 0.0,
+            roughness: self.data.roughness,
+            albedo: self.data.scatter_color,
+            emissive: [0.0; 3],
+            opacity: 1.0,
+        })
+    }
+
+    fn set_params(&mut self, params: MaterialParams) {
+        self.data.roughness = params.roughness;
+        self.data.scatter_color = params.albedo;
+    }
 }
 
 const DIR_LIGHT: DirectionalLight = DirectionalLight {
@@ -340,12 +755,6 @@ const DIR_LIGHT: DirectionalLight = DirectionalLight {
     color: [1.0, 1.0, 1.0, 1.0],
 };
 
-const POINT_LIGHT: PointLight = PointLight {
-    position: [0.0, -0.01, 0.01, 0.0],
-    color: [1.0, 1.0, 1.0, 1.0],
-    distance_decay: [10.0, 0.8, 0.0, 0.0],
-};
-
 impl MaterialBasic {
     pub fn new(ctx: &mut render::RenderContext) -> Result<Self, gfx::PipelineStateError<String>> {
         let glsl = piston_window::OpenGL::V3_2.to_glsl();
@@ -389,6 +798,8 @@ impl MaterialTrait<Vertex> for MaterialBasic {
         (&geometry.vertices, geometry.indices.as_slice());
         let matrix = ctx.projection * ctx.view * model_matrix;
         self.data.vbuf = vbuf;
+        self.data.out_color = ctx.output_color.clone();
+        self.data.out_depth = ctx.output_stencil.clone();
         self.data.u_model_view_proj = matrix.into();
         encoder.draw(&slice, &self.pso, &self.data);
     }
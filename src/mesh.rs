@@ -1,6 +1,237 @@
 use gfx::{self, traits::*, pso};
+use cgmath::{self, prelude::*};
+use material;
+use piston_window;
+use render;
 
 pub struct Geometry<V: Pod> {
     pub vertices: Vec<V>,
     pub indices: Vec<u32>,
-}
\ No newline at end of file
+}
+
+pub trait Position {
+    fn position(&self) -> [f32; 3];
+}
+
+impl<V: Pod + Position> Geometry<V> {
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|v| v.position()))
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.bounds().map(|b| {
+            let center = b.center();
+            let radius = self.vertices
+                .iter()
+                .map(|v| (cgmath::Vector3::from(v.position()) - center).magnitude())
+                .fold(0.0, f32::max);
+            BoundingSphere { center, radius }
+        })
+    }
+
+    // segments where the triangles (indices taken as a triangle list) cross a plane,
+    // with the vertices placed by `mat`
+    pub fn slice(&self, mat: cgmath::Matrix4<f32>, point: cgmath::Vector3<f32>, normal: cgmath::Vector3<f32>) -> Vec<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)> {
+        let positions: Vec<cgmath::Vector3<f32>> = self.vertices
+            .iter()
+            .map(|v| (mat * cgmath::Vector3::from(v.position()).extend(1.0)).truncate())
+            .collect();
+        let distances: Vec<f32> = positions.iter().map(|&p| (p - point).dot(normal)).collect();
+        let mut segments = Vec::new();
+        for tri in self.indices.chunks(3).filter(|tri| tri.len() == 3) {
+            let mut crossings = Vec::with_capacity(2);
+            for &(a, b) in &[(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])] {
+                let (da, db) = (distances[a as usize], distances[b as usize]);
+                if (da < 0.0) != (db < 0.0) {
+                    let t = da / (da - db);
+                    crossings.push(positions[a as usize].lerp(positions[b as usize], t));
+                }
+            }
+            if crossings.len() == 2 {
+                segments.push((crossings[0], crossings[1]));
+            }
+        }
+        segments
+    }
+}
+
+// geometry with its material, an offset within the owner and cached local bounds
+pub struct Mesh<V: Pod> {
+    pub geometry: Geometry<V>,
+    pub material: Box<material::MaterialTrait<V>>,
+    pub model_view: cgmath::Matrix4<f32>,
+    pub bounds: Option<Aabb>,
+    pub sphere: Option<BoundingSphere>,
+}
+
+impl<V: Pod + Position> Mesh<V> {
+    pub fn new(geometry: Geometry<V>, material: Box<material::MaterialTrait<V>>) -> Self {
+        let bounds = geometry.bounds();
+        let sphere = geometry.bounding_sphere();
+        Mesh {
+            geometry,
+            material,
+            model_view: cgmath::Matrix4::from_scale(1.0),
+            bounds,
+            sphere,
+        }
+    }
+}
+
+impl<V: Pod> Mesh<V> {
+    // culled against the view frustum when bounds are known
+    pub fn draw(&mut self,
+                ctx: &mut render::RenderContext,
+                encoder: &mut piston_window::GfxEncoder,
+                parent: cgmath::Matrix4<f32>,
+    ) {
+        let model = parent * self.model_view;
+        if self.material.see_through() {
+            ctx.request_scene_copy();
+        }
+        if let (Some(bounds), Some(sphere)) = (self.bounds, self.sphere) {
+            if !ctx.is_visible(&sphere.transform(model), &bounds.transform(model)) {
+                return;
+            }
+        }
+        self.material.draw(ctx, encoder, &self.geometry, model);
+    }
+}
+
+pub fn parts_bounds<V: Pod>(parts: &[Mesh<V>]) -> Option<Aabb> {
+    parts
+        .iter()
+        .filter_map(|part| part.bounds.map(|b| b.transform(part.model_view)))
+        .fold(None, |acc: Option<Aabb>, b| {
+            Some(acc.map_or(b, |a| a.union(&b)))
+        })
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: cgmath::Vector3<f32>,
+    pub max: cgmath::Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: cgmath::Vector3<f32>, max: cgmath::Vector3<f32>) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_points<I: Iterator<Item=[f32; 3]>>(points: I) -> Option<Self> {
+        let mut bounds: Option<Aabb> = None;
+        for p in points {
+            let p = cgmath::Vector3::from(p);
+            bounds = Some(match bounds {
+                Some(b) => b.extend(p),
+                None => Aabb::new(p, p),
+            });
+        }
+        bounds
+    }
+
+    pub fn extend(&self, p: cgmath::Vector3<f32>) -> Self {
+        Aabb {
+            min: cgmath::Vector3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z)),
+            max: cgmath::Vector3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z)),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.extend(other.min).extend(other.max)
+    }
+
+    pub fn center(&self) -> cgmath::Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> cgmath::Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.size().magnitude() * 0.5
+    }
+
+    pub fn corners(&self) -> [cgmath::Vector3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            cgmath::Vector3::new(a.x, a.y, a.z),
+            cgmath::Vector3::new(b.x, a.y, a.z),
+            cgmath::Vector3::new(a.x, b.y, a.z),
+            cgmath::Vector3::new(b.x, b.y, a.z),
+            cgmath::Vector3::new(a.x, a.y, b.z),
+            cgmath::Vector3::new(b.x, a.y, b.z),
+            cgmath::Vector3::new(a.x, b.y, b.z),
+            cgmath::Vector3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // bounds of the 8 transformed corners; conservative for rotations
+    pub fn transform(&self, mat: cgmath::Matrix4<f32>) -> Self {
+        Aabb::from_points(
+            self.corners()
+                .iter()
+                .map(|c| (mat * c.extend(1.0)).truncate().into())
+        ).unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: cgmath::Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn from_aabb(bounds: &Aabb) -> Self {
+        BoundingSphere {
+            center: bounds.center(),
+            radius: bounds.radius(),
+        }
+    }
+
+    // radius is scaled by the largest axis scale, so the result stays conservative
+    pub fn transform(&self, mat: cgmath::Matrix4<f32>) -> Self {
+        let scale = mat.x.truncate().magnitude()
+            .max(mat.y.truncate().magnitude())
+            .max(mat.z.truncate().magnitude());
+        BoundingSphere {
+            center: (mat * self.center.extend(1.0)).truncate(),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: cgmath::Vector3<f32>,
+    pub direction: cgmath::Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: cgmath::Vector3<f32>, direction: cgmath::Vector3<f32>) -> Self {
+        Ray { origin, direction: direction.normalize() }
+    }
+
+    pub fn at(&self, t: f32) -> cgmath::Vector3<f32> {
+        self.origin + self.direction * t
+    }
+
+    // the direction is not renormalized, so `t` keeps meaning the same point
+    pub fn transform(&self, mat: cgmath::Matrix4<f32>) -> Self {
+        Ray {
+            origin: (mat * self.origin.extend(1.0)).truncate(),
+            direction: (mat * self.direction.extend(0.0)).truncate(),
+        }
+    }
+
+    // distance to the plane y = height, if it is hit in front of the origin
+    pub fn intersect_horizontal(&self, height: f32) -> Option<f32> {
+        if self.direction.y.abs() < 1e-6 {
+            return None;
+        }
+        let t = (height - self.origin.y) / self.direction.y;
+        if t >= 0.0 { Some(t) } else { None }
+    }
+}
@@ -0,0 +1,196 @@
+use std;
+use std::io;
+use std::path::Path;
+use ron;
+use fluid::Disturbance;
+
+pub const DEFAULT_CONFIG_FILE: &str = "mg-rs.ron";
+
+pub const USAGE: &str = "usage: mg-rs-piston [SCENE.ron | EXPORTED.png] [options]
+
+  --config PATH     defaults for every option below (default: ./mg-rs.ron if present)
+  --scene PATH      scene file or exported png to load
+  --sketch NAME     registered sketch to start with
+  --obj PATH        extra OBJ model to add to the scene, may be repeated
+  --width N         window width
+  --height N        window height
+  --fullscreen      start fullscreen
+  --vsync           wait for vertical sync
+  --no-vsync        don't wait for vertical sync
+  --samples N       MSAA samples
+  --seed N          random seed
+  --headless        render one image to --output and exit
+  --frames N        frames simulated before a headless render
+  --output PATH     image for --headless, directory for --batch
+  --batch PATH      run a batch description and exit
+  --drop-strength X --drop-radius X
+                    right click on the water
+  --drag-strength X --drag-radius X
+                    dragging with the right button held
+  --help            show this message";
+
+// loaded from the config file, then overridden by command-line flags
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub sketch: Option<String>,
+    pub objs: Vec<String>,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub samples: u8,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub frames: u32,
+    pub output: Option<String>,
+    pub batch: Option<String>,
+    pub drop: Disturbance,
+    pub drag: Disturbance,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene: None,
+            sketch: None,
+            objs: Vec::new(),
+            width: 1024,
+            height: 768,
+            fullscreen: false,
+            vsync: true,
+            samples: 4,
+            seed: None,
+            headless: false,
+            frames: 120,
+            output: None,
+            batch: None,
+            drop: Disturbance { strength: 0.5, radius: 0.05 },
+            drag: Disturbance { strength: 0.05, radius: 0.03 },
+        }
+    }
+}
+
+impl Options {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        ron::de::from_reader(file).map_err(|e| invalid_input(e.to_string()))
+    }
+
+    // Ok(None) when only the usage was asked for
+    pub fn from_env() -> io::Result<Option<Self>> {
+        Options::from_args(std::env::args().skip(1).collect())
+    }
+
+    pub fn from_args(args: Vec<String>) -> io::Result<Option<Self>> {
+        let config = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Some(args.get(i + 1).cloned().ok_or_else(|| missing_value("--config"))?),
+            None => None,
+        };
+        let mut options = match config {
+            Some(path) => Options::load(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Options::load(DEFAULT_CONFIG_FILE)?,
+            None => Options::default(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" | "-h" => return Ok(None),
+                "--config" => {
+                    args.next();
+                }
+                "--scene" => options.scene = Some(value(&mut args, &arg)?),
+                "--sketch" => options.sketch = Some(value(&mut args, &arg)?),
+                "--obj" => options.objs.push(value(&mut args, &arg)?),
+                "--width" => options.width = parse(&mut args, &arg)?,
+                "--height" => options.height = parse(&mut args, &arg)?,
+                "--fullscreen" => options.fullscreen = true,
+                "--vsync" => options.vsync = true,
+                "--no-vsync" => options.vsync = false,
+                "--samples" => options.samples = parse(&mut args, &arg)?,
+                "--seed" => options.seed = Some(parse(&mut args, &arg)?),
+                "--headless" => options.headless = true,
+                "--frames" => options.frames = parse(&mut args, &arg)?,
+                "--output" => options.output = Some(value(&mut args, &arg)?),
+                "--batch" => options.batch = Some(value(&mut args, &arg)?),
+                "--drop-strength" => options.drop.strength = parse(&mut args, &arg)?,
+                "--drop-radius" => options.drop.radius = parse(&mut args, &arg)?,
+                "--drag-strength" => options.drag.strength = parse(&mut args, &arg)?,
+                "--drag-radius" => options.drag.radius = parse(&mut args, &arg)?,
+                _ if arg.starts_with("--") => return Err(invalid_input(format!("unknown option: {}", arg))),
+                _ => options.scene = Some(arg.clone()),
+            }
+        }
+        Ok(Some(options))
+    }
+}
+
+fn value<I: Iterator<Item=String>>(args: &mut I, name: &str) -> io::Result<String> {
+    args.next().ok_or_else(|| missing_value(name))
+}
+
+fn parse<T: std::str::FromStr, I: Iterator<Item=String>>(args: &mut I, name: &str) -> io::Result<T> {
+    let text = value(args, name)?;
+    text.parse().map_err(|_| invalid_input(format!("invalid value for {}: {}", name, text)))
+}
+
+fn missing_value(name: &str) -> io::Error {
+    invalid_input(format!("missing value for {}", name))
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std;
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn config(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn config_fills_unset_options() {
+        let path = config("mg-rs-test-fill.ron", "(width: 800, vsync: false, sketch: Some(\"water\"))");
+        let options = Options::from_args(args(&["--config", &path])).unwrap().unwrap();
+        assert_eq!(options.width, 800);
+        assert!(!options.vsync);
+        assert_eq!(options.sketch, Some("water".to_string()));
+        assert_eq!(options.height, Options::default().height);
+    }
+
+    #[test]
+    fn flags_override_config() {
+        let path = config("mg-rs-test-override.ron", "(width: 800, height: 600, vsync: true, seed: Some(1))");
+        let options = Options::from_args(args(&["--width", "640", "--no-vsync", "--seed", "7", "--config", &path]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.width, 640);
+        assert_eq!(options.height, 600);
+        assert!(!options.vsync);
+        assert_eq!(options.seed, Some(7));
+    }
+
+    #[test]
+    fn objs_from_flags_extend_config() {
+        let path = config("mg-rs-test-objs.ron", "(objs: [\"a.obj\"])");
+        let options = Options::from_args(args(&["--config", &path, "--obj", "b.obj"])).unwrap().unwrap();
+        assert_eq!(options.objs, vec!["a.obj".to_string(), "b.obj".to_string()]);
+    }
+
+    #[test]
+    fn missing_config_is_an_error() {
+        assert!(Options::from_args(args(&["--config", "does-not-exist.ron"])).is_err());
+        assert!(Options::from_args(args(&["--config"])).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::io;
+use fluid::Boundary;
+use material::MaterialParams;
+use render::RenderContext;
+use world::{BasicWorld, World};
+
+// tweakable values addressed as "camera.fov", "mirror.distortion" or "<entity name>.<field>",
+// e.g. "water.roughness", "water.albedo.g", "water.damping", "Eames_chair.y", "Eames_chair.scale"
+pub fn apply(world: &mut BasicWorld, ctx: &mut RenderContext, key: &str, value: f32) -> io::Result<()> {
+    if key == "camera.fov" {
+        ctx.set_fov(value);
+        return Ok(());
+    }
+    if key == "mirror.distortion" {
+        ctx.reflection_distortion = value;
+        return Ok(());
+    }
+    let (name, field) = match key.find('.') {
+        Some(i) => (&key[..i], &key[i + 1..]),
+        None => return Err(invalid_param(key)),
+    };
+    let id = world.find(name).ok_or_else(|| invalid_param(key))?;
+
+    match field {
+        "x" | "y" | "z" | "scale" => {
+            let transform = world.transform_mut(id).ok_or_else(|| invalid_param(key))?;
+            match field {
+                "x" => transform.translation.x = value,
+                "y" => transform.translation.y = value,
+                "z" => transform.translation.z = value,
+                _ => transform.set_uniform_scale(value),
+            }
+        }
+        "height_scale" | "wave_speed" | "viscosity" | "damping" | "ambient_wave" | "boundary" => {
+            let mut params = world.fluid_params(id).ok_or_else(|| invalid_param(key))?;
+            match field {
+                "height_scale" => params.height_scale = value,
+                "wave_speed" => params.wave_speed = value,
+                "viscosity" => params.viscosity = value,
+                "damping" => params.damping = value,
+                "ambient_wave" => params.ambient_wave = value as f64,
+                _ => {
+                    params.boundary = match value as u32 {
+                        0 => Boundary::Reflective,
+                        1 => Boundary::Absorbing,
+                        _ => Boundary::Periodic,
+                    }
+                }
+            }
+            world.set_fluid_params(id, params);
+        }
+        "refraction" | "foam" | "caustics" | "reflection" => {
+            let mut params = world.water_params(id).ok_or_else(|| invalid_param(key))?;
+            match field {
+                "refraction" => params.refraction = value,
+                "foam" => params.foam = value,
+                "caustics" => params.caustics = value,
+                _ => params.reflection = value,
+            }
+            world.set_water_params(id, params);
+        }
+        "metallic" | "roughness" | "opacity" | "albedo.r" | "albedo.g" | "albedo.b"
+        | "emissive.r" | "emissive.g" | "emissive.b" => {
+            world.material_params(id).ok_or_else(|| invalid_param(key))?;
+            // only the named field, so parts with their own materials keep the rest
+            world.edit_material_params(id, &|params: &mut MaterialParams| match field {
+                "metallic" => params.metallic = value,
+                "roughness" => params.roughness = value,
+                "opacity" => params.opacity = value,
+                "albedo.r" => params.albedo[0] = value,
+                "albedo.g" => params.albedo[1] = value,
+                "albedo.b" => params.albedo[2] = value,
+                "emissive.r" => params.emissive[0] = value,
+                "emissive.g" => params.emissive[1] = value,
+                _ => params.emissive[2] = value,
+            });
+        }
+        _ => return Err(invalid_param(key)),
+    }
+    Ok(())
+}
+
+fn invalid_param(key: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown parameter: {}", key))
+}
@@ -0,0 +1,88 @@
+use rand::{self, SeedableRng};
+use rand::prng::ChaChaRng;
+
+// one seed per scene; every consumer draws from its own named stream so that
+// adding a generator doesn't change the numbers the others see
+#[derive(Clone, Copy, Debug)]
+pub struct Random {
+    seed: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { seed }
+    }
+
+    pub fn from_entropy() -> Self {
+        Random::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reroll(&mut self) {
+        self.seed = rand::random();
+    }
+
+    pub fn stream(&self, name: &str) -> ChaChaRng {
+        let mut state = self.seed ^ fnv1a(name);
+        let mut bytes = [0u8; 32];
+        for chunk in bytes.chunks_mut(8) {
+            let value = splitmix64(&mut state);
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = (value >> (i * 8)) as u8;
+            }
+        }
+        ChaChaRng::from_seed(bytes)
+    }
+}
+
+// stable across platforms and compiler versions, unlike the std hasher
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    fn draw(random: Random, name: &str) -> Vec<u64> {
+        let mut rng = random.stream(name);
+        (0..8).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_seed_and_name_give_the_same_stream() {
+        assert_eq!(draw(Random::new(7), "water"), draw(Random::new(7), "water"));
+        // independent of other streams drawn before it
+        let random = Random::new(7);
+        draw(random, "chair");
+        assert_eq!(draw(random, "water"), draw(Random::new(7), "water"));
+    }
+
+    #[test]
+    fn names_and_seeds_give_different_streams() {
+        assert!(draw(Random::new(7), "water") != draw(Random::new(7), "chair"));
+        assert!(draw(Random::new(7), "water") != draw(Random::new(8), "water"));
+        assert!(draw(Random::new(0), "#0") != draw(Random::new(0), "#1"));
+    }
+
+    #[test]
+    fn name_hash_is_the_reference_fnv1a() {
+        assert_eq!(fnv1a(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a("foobar"), 0x8594_4171_f739_67e8);
+    }
+}
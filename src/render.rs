@@ -1,6 +1,7 @@
 extern crate gfx_device_gl;
 
-use cgmath;
+use std;
+use cgmath::{self, prelude::*};
 use gfx::{self, traits::*};
 use piston_window::{self, Window};
 use super::world;
@@ -8,14 +9,152 @@ use shader_version::Shaders;
 use shader_version::glsl::GLSL;
 use camera_controllers::{CameraPerspective};
 use material;
+use mesh;
+use frustum::Frustum;
+use world::EntityId;
+
+// matches LIGHT_MAX in the pbr shaders
+pub const MAX_LIGHTS: usize = 4;
+// reflections are drawn at this fraction of the output size
+const REFLECTION_DIVISOR: u32 = 2;
+// moves the reflection clip plane below the surface, hiding seams where objects cross it
+const REFLECTION_CLIP_BIAS: f32 = 0.01;
+// default for `RenderContext::reflection_distortion`
+pub const REFLECTION_DISTORTION: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+type Resources = gfx_device_gl::Resources;
+
+// which drawing of the world is in progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    Main,
+    // the scene behind see-through surfaces, which skip this pass
+    SceneCopy,
+    // the scene mirrored across the plane of an entity, which skips this pass
+    Reflection(EntityId),
+}
+
+// color and depth of the scene that materials can sample, e.g. for refraction
+pub struct SceneCopy {
+    pub width: u32,
+    pub height: u32,
+    pub color: gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+    pub depth: gfx::handle::ShaderResourceView<Resources, f32>,
+    color_target: gfx::handle::RenderTargetView<Resources, gfx::format::Srgba8>,
+    depth_target: gfx::handle::DepthStencilView<Resources, gfx::format::DepthStencil>,
+}
+
+impl SceneCopy {
+    pub fn new(factory: &mut gfx_device_gl::Factory, width: u32, height: u32) -> Self {
+        let (_, color, color_target) = factory
+            .create_render_target::<gfx::format::Srgba8>(width as u16, height as u16)
+            .unwrap();
+        let (_, depth, depth_target) = factory
+            .create_depth_stencil::<gfx::format::DepthStencil>(width as u16, height as u16)
+            .unwrap();
+        SceneCopy { width, height, color, depth, color_target, depth_target }
+    }
+}
+
+// color and depth targets that are not shown on screen, read back after drawing
+pub struct Offscreen {
+    pub width: u32,
+    pub height: u32,
+    texture: gfx::handle::Texture<Resources, gfx::format::R8_G8_B8_A8>,
+    color: gfx::handle::RenderTargetView<Resources, gfx::format::Srgba8>,
+    depth: gfx::handle::DepthStencilView<Resources, gfx::format::DepthStencil>,
+    download: gfx::handle::Buffer<Resources, [u8; 4]>,
+}
+
+impl Offscreen {
+    pub fn new(factory: &mut gfx_device_gl::Factory, width: u32, height: u32) -> Self {
+        let (texture, _, color) = factory
+            .create_render_target::<gfx::format::Srgba8>(width as u16, height as u16)
+            .unwrap();
+        let depth = factory
+            .create_depth_stencil_view_only::<gfx::format::DepthStencil>(width as u16, height as u16)
+            .unwrap();
+        let download = factory
+            .create_download_buffer::<[u8; 4]>(width as usize * height as usize)
+            .unwrap();
+        Offscreen { width, height, texture, color, depth, download }
+    }
+
+    fn copy(&self, encoder: &mut piston_window::GfxEncoder) {
+        let info = self.texture.get_info().to_raw_image_info(gfx::format::ChannelType::Srgb, 0);
+        encoder
+            .copy_texture_to_buffer_raw(self.texture.raw(), None, info, self.download.raw(), 0)
+            .unwrap();
+    }
+
+    // RGBA8 rows, top row first
+    fn read(&self, factory: &mut gfx_device_gl::Factory) -> Vec<u8> {
+        let reader = factory.read_mapping(&self.download).unwrap();
+        let width = self.width as usize;
+        let mut pixels = Vec::with_capacity(reader.len() * 4);
+        for row in reader.chunks(width).rev() {
+            for pixel in row {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+        pixels
+    }
+}
+
+// mirrored view of the scene for one reflective entity, sampled at screen positions
+pub struct Reflection {
+    pub owner: EntityId,
+    pub width: u32,
+    pub height: u32,
+    // world-space normal of the mirror plane
+    pub normal: cgmath::Vector3<f32>,
+    pub color: gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+    color_target: gfx::handle::RenderTargetView<Resources, gfx::format::Srgba8>,
+    depth_target: gfx::handle::DepthStencilView<Resources, gfx::format::DepthStencil>,
+}
+
+impl Reflection {
+    pub fn new(factory: &mut gfx_device_gl::Factory, owner: EntityId, width: u32, height: u32) -> Self {
+        let (_, color, color_target) = factory
+            .create_render_target::<gfx::format::Srgba8>(width as u16, height as u16)
+            .unwrap();
+        let depth_target = factory
+            .create_depth_stencil_view_only::<gfx::format::DepthStencil>(width as u16, height as u16)
+            .unwrap();
+        Reflection { owner, width, height, normal: cgmath::Vector3::unit_y(), color, color_target, depth_target }
+    }
+}
 
 pub struct RenderContext{
     pub factory: gfx_device_gl::Factory,
     pub projection: cgmath::Matrix4<f32>,
+    pub fov: f32,
+    pub aspect_ratio: f32,
     pub view: cgmath::Matrix4<f32>,
+    pub frustum: Frustum,
+    pub stats: RenderStats,
+    pub background: [f32; 4],
+    pub directional_lights: Vec<material::DirectionalLight>,
     pub point_lights: Vec<material::PointLight>,
+    // screen-space offset of mirror reflections per unit of normal tilt away from the plane
+    pub reflection_distortion: f32,
     pub output_color: gfx::handle::RenderTargetView<gfx_device_gl::Resources, gfx::format::Srgba8>,
     pub output_stencil: gfx::handle::DepthStencilView<gfx_device_gl::Resources, gfx::format::DepthStencil>,
+    pub pass: Pass,
+    // seconds of drawing, for animated materials
+    pub time: f32,
+    // entity whose materials are drawing
+    pub drawing: Option<EntityId>,
+    scene_copy: Option<SceneCopy>,
+    // asked for during the previous frame
+    scene_copy_requested: bool,
+    reflections: Vec<Reflection>,
 }
 
 impl RenderContext {
@@ -24,40 +163,272 @@ impl RenderContext {
         let width = window.draw_size().width;
         let height = window.draw_size().height;
 
-        let projection: cgmath::Matrix4<f32> = cgmath::Matrix4::from(CameraPerspective {
-            fov: 90.0, near_clip: 0.1, far_clip: 1000.0,
-            aspect_ratio: (width as f32) / (height as f32)
-        }.projection());
+        let fov = 90.0;
+        let aspect_ratio = (width as f32) / (height as f32);
 
         let opengl = piston_window::OpenGL::V3_2;
         let glsl = opengl.to_glsl();
 
         RenderContext {
             factory,
-            projection,
+            projection: perspective(fov, aspect_ratio),
+            fov,
+            aspect_ratio,
             view: cgmath::Matrix4::from_scale(1.0),
+            frustum: Frustum::from_matrix(perspective(fov, aspect_ratio)),
+            stats: RenderStats::default(),
+            background: [0.3, 0.3, 0.3, 1.0],
+            directional_lights: Vec::new(),
             point_lights: Vec::new(),
+            reflection_distortion: REFLECTION_DISTORTION,
             output_color: window.output_color.clone(),
             output_stencil: window.output_stencil.clone(),
+            pass: Pass::Main,
+            time: 0.0,
+            drawing: None,
+            scene_copy: None,
+            scene_copy_requested: false,
+            reflections: Vec::new(),
         }
     }
 
     pub fn resize(&mut self, window: &piston_window::PistonWindow, x: u32, y: u32) {
-        self.projection = cgmath::Matrix4::from(CameraPerspective {
-            fov: 90.0, near_clip: 0.1, far_clip: 1000.0,
-            aspect_ratio: (x as f32) / (y as f32)
-        }.projection());
+        self.aspect_ratio = (x as f32) / (y as f32);
+        self.projection = perspective(self.fov, self.aspect_ratio);
         self.output_color = window.output_color.clone();
         self.output_stencil = window.output_stencil.clone();
     }
 
     pub fn draw_world<T: world::World>(&mut self, world: &mut T, window: &mut piston_window::PistonWindow, dt: f64) {
-        window.encoder.clear(&window.output_color, [0.3, 0.3, 0.3, 1.0]);
-        window.encoder.clear_depth(&window.output_stencil, 1.0);
-        world.draw(self, &mut window.encoder, dt);
+        self.draw_to(world, &mut window.encoder, dt);
+    }
+
+    // draws into the current output targets
+    fn draw_to<T: world::World>(&mut self, world: &mut T, encoder: &mut piston_window::GfxEncoder, dt: f64) {
+        self.time += dt as f32;
+        self.draw_reflections(world, encoder, dt);
+        if std::mem::replace(&mut self.scene_copy_requested, false) {
+            self.draw_scene_copy(world, encoder, dt);
+        }
+        encoder.clear(&self.output_color, self.background);
+        encoder.clear_depth(&self.output_stencil, 1.0);
+        self.frustum = Frustum::from_matrix(self.projection * self.view);
+        self.stats = RenderStats::default();
+        world.draw(self, encoder, dt);
+    }
+
+    // the next frame first draws the scene without see-through surfaces; entities
+    // holding such a surface ask every frame, visible or not, so the copy stops
+    // once the last one is gone
+    pub fn request_scene_copy(&mut self) {
+        self.scene_copy_requested = true;
+    }
+
+    // the copy made for the frame being drawn
+    pub fn scene_copy(&self) -> Option<&SceneCopy> {
+        self.scene_copy.as_ref()
+    }
+
+    // reflection of the entity being drawn, not available while reflections are drawn
+    pub fn reflection(&self) -> Option<&Reflection> {
+        match (self.pass, self.drawing) {
+            (Pass::Reflection(_), _) | (_, None) => None,
+            (_, Some(id)) => self.reflections.iter().find(|r| r.owner == id),
+        }
+    }
+
+    // size of the current output target
+    pub fn output_size(&self) -> (u32, u32) {
+        let (width, height, _, _) = self.output_color.get_dimensions();
+        (width as u32, height as u32)
+    }
+
+    fn draw_scene_copy<T: world::World>(&mut self, world: &mut T, encoder: &mut piston_window::GfxEncoder, dt: f64) {
+        let (width, height) = self.output_size();
+        let resized = self.scene_copy.as_ref().map_or(true, |copy| copy.width != width || copy.height != height);
+        if resized {
+            self.scene_copy = Some(SceneCopy::new(&mut self.factory, width, height));
+        }
+        let (color_target, depth_target) = {
+            let copy = self.scene_copy.as_ref().unwrap();
+            (copy.color_target.clone(), copy.depth_target.clone())
+        };
+        let color = std::mem::replace(&mut self.output_color, color_target);
+        let depth = std::mem::replace(&mut self.output_stencil, depth_target);
+        self.pass = Pass::SceneCopy;
+
+        encoder.clear(&self.output_color, self.background);
+        encoder.clear_depth(&self.output_stencil, 1.0);
+        self.frustum = Frustum::from_matrix(self.projection * self.view);
+        world.draw(self, encoder, dt);
+
+        self.pass = Pass::Main;
+        self.output_color = color;
+        self.output_stencil = depth;
+    }
+
+    fn draw_reflections<T: world::World>(&mut self, world: &mut T, encoder: &mut piston_window::GfxEncoder, dt: f64) {
+        let planes = world.reflection_planes();
+        self.reflections.retain(|r| planes.iter().any(|&(id, _)| id == r.owner));
+        if planes.is_empty() {
+            return;
+        }
+        let (width, height) = self.output_size();
+        let (width, height) = ((width / REFLECTION_DIVISOR).max(1), (height / REFLECTION_DIVISOR).max(1));
+        let (view, projection) = (self.view, self.projection);
+        let background = self.background;
+
+        for (owner, (point, normal)) in planes {
+            let index = match self.reflections.iter().position(|r| r.owner == owner) {
+                Some(i) if self.reflections[i].width == width && self.reflections[i].height == height => i,
+                Some(i) => {
+                    self.reflections[i] = Reflection::new(&mut self.factory, owner, width, height);
+                    i
+                }
+                None => {
+                    self.reflections.push(Reflection::new(&mut self.factory, owner, width, height));
+                    self.reflections.len() - 1
+                }
+            };
+            let normal = normal.normalize();
+            self.reflections[index].normal = normal;
+            let mirrored = view * mirror(point, normal);
+            let inverse = match mirrored.invert() {
+                Some(inverse) => inverse,
+                None => continue,
+            };
+            // keep what is above the plane, seen through the mirror
+            let plane = normal.extend(-normal.dot(point) + REFLECTION_CLIP_BIAS);
+            self.view = mirrored;
+            self.projection = oblique(projection, inverse.transpose() * plane);
+
+            let (color_target, depth_target) = (self.reflections[index].color_target.clone(), self.reflections[index].depth_target.clone());
+            let color = std::mem::replace(&mut self.output_color, color_target);
+            let depth = std::mem::replace(&mut self.output_stencil, depth_target);
+            self.pass = Pass::Reflection(owner);
+
+            // alpha stays 0 where nothing is reflected, so surfaces can fall back to their sky
+            encoder.clear(&self.output_color, [background[0], background[1], background[2], 0.0]);
+            encoder.clear_depth(&self.output_stencil, 1.0);
+            self.frustum = Frustum::from_matrix(self.projection * self.view);
+            world.draw(self, encoder, dt);
+
+            self.pass = Pass::Main;
+            self.output_color = color;
+            self.output_stencil = depth;
+        }
+        self.view = view;
+        self.projection = projection;
+    }
+
+    // renders one frame into `target` and returns its pixels; the window targets are left untouched
+    pub fn render_offscreen<T: world::World>(&mut self, world: &mut T, target: &Offscreen, window: &mut piston_window::PistonWindow) -> Vec<u8> {
+        let color = std::mem::replace(&mut self.output_color, target.color.clone());
+        let depth = std::mem::replace(&mut self.output_stencil, target.depth.clone());
+        let aspect_ratio = self.aspect_ratio;
+        self.aspect_ratio = target.width as f32 / target.height as f32;
+        self.projection = perspective(self.fov, self.aspect_ratio);
+
+        self.draw_to(world, &mut window.encoder, 0.0);
+        target.copy(&mut window.encoder);
+        window.encoder.flush(&mut window.device);
+
+        self.output_color = color;
+        self.output_stencil = depth;
+        self.aspect_ratio = aspect_ratio;
+        self.projection = perspective(self.fov, self.aspect_ratio);
+        target.read(&mut self.factory)
+    }
+
+    // world-space bounds test against the current frustum, counted in `stats`
+    pub fn is_visible(&mut self, sphere: &mesh::BoundingSphere, bounds: &mesh::Aabb) -> bool {
+        let visible = self.frustum.intersects_sphere(sphere) && self.frustum.intersects_aabb(bounds);
+        if visible {
+            self.stats.drawn += 1;
+        } else {
+            self.stats.culled += 1;
+        }
+        visible
+    }
+
+    // world-space ray through a window position in pixels, origin on the near plane
+    pub fn screen_ray(&self, x: f64, y: f64, width: f64, height: f64) -> Option<mesh::Ray> {
+        let inverse = (self.projection * self.view).invert()?;
+        let ndc_x = (2.0 * x / width - 1.0) as f32;
+        let ndc_y = (1.0 - 2.0 * y / height) as f32;
+        let unproject = |z: f32| {
+            let p = inverse * cgmath::Vector4::new(ndc_x, ndc_y, z, 1.0);
+            p.truncate() / p.w
+        };
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        Some(mesh::Ray::new(near, far - near))
     }
 
     pub fn set_view(&mut self, view: cgmath::Matrix4<f32>) {
         self.view = view;
     }
+
+    // without scene lights the materials fall back to their built-in lighting
+    pub fn has_lights(&self) -> bool {
+        !self.directional_lights.is_empty() || !self.point_lights.is_empty()
+    }
+
+    pub fn view_directional_lights(&self) -> Vec<material::DirectionalLight> {
+        let v = self.view;
+        let rotation = cgmath::Matrix3::from_cols(v.x.truncate(), v.y.truncate(), v.z.truncate());
+        self.directional_lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .map(|l| l.transform(rotation))
+            .collect()
+    }
+
+    pub fn view_point_lights(&self) -> Vec<material::PointLight> {
+        self.point_lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .map(|l| l.transform(self.view))
+            .collect()
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov;
+        self.projection = perspective(self.fov, self.aspect_ratio);
+    }
+}
+
+// reflection across the plane through `point` with unit `normal`
+fn mirror(point: cgmath::Vector3<f32>, normal: cgmath::Vector3<f32>) -> cgmath::Matrix4<f32> {
+    let (n, d) = (normal, -normal.dot(point));
+    cgmath::Matrix4::new(
+        1.0 - 2.0 * n.x * n.x, -2.0 * n.x * n.y, -2.0 * n.x * n.z, 0.0,
+        -2.0 * n.x * n.y, 1.0 - 2.0 * n.y * n.y, -2.0 * n.y * n.z, 0.0,
+        -2.0 * n.x * n.z, -2.0 * n.y * n.z, 1.0 - 2.0 * n.z * n.z, 0.0,
+        -2.0 * d * n.x, -2.0 * d * n.y, -2.0 * d * n.z, 1.0,
+    )
+}
+
+// moves the near plane of an OpenGL projection onto a view-space clip plane (Lengyel's method)
+fn oblique(projection: cgmath::Matrix4<f32>, plane: cgmath::Vector4<f32>) -> cgmath::Matrix4<f32> {
+    let inverse = match projection.invert() {
+        Some(inverse) => inverse,
+        None => return projection,
+    };
+    let q = inverse * cgmath::Vector4::new(plane.x.signum(), plane.y.signum(), 1.0, 1.0);
+    let c = plane * (2.0 / plane.dot(q));
+    let mut result = projection;
+    // third row becomes c minus the fourth row
+    result.x.z = c.x - result.x.w;
+    result.y.z = c.y - result.y.w;
+    result.z.z = c.z - result.z.w;
+    result.w.z = c.w - result.w.w;
+    result
+}
+
+fn perspective(fov: f32, aspect_ratio: f32) -> cgmath::Matrix4<f32> {
+    cgmath::Matrix4::from(CameraPerspective {
+        fov, near_clip: 0.1, far_clip: 1000.0,
+        aspect_ratio,
+    }.projection())
 }
\ No newline at end of file
@@ -0,0 +1,348 @@
+use std;
+use std::io;
+use std::path::Path;
+use cgmath;
+use ron;
+use camera::{CameraMode, CameraRig};
+use entity::entity_obj;
+use material::{MaterialParams, WaterParams};
+use fluid::{FluidParams, FluidSurface, SolverKind};
+use component::{Buoyancy, Collider, FloatParams, Light, Renderable, WorldLight};
+use render::RenderContext;
+use transform::Transform;
+use world::{BasicWorld, EntityId};
+use random::Random;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneDesc {
+    // random seed when none is given on the command line
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default = "default_background")]
+    pub background: [f32; 4],
+    #[serde(default)]
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub lights: Vec<LightDesc>,
+    #[serde(default)]
+    pub entities: Vec<EntityDesc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub mode: CameraMode,
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub fov: f32,
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        CameraDesc {
+            mode: CameraMode::Fly,
+            position: [0.5, 0.5, 1.0],
+            target: [0.5, 0.5, 0.0],
+            fov: 90.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LightDesc {
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+    },
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        distance: f32,
+        decay: f32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum EntityKind {
+    Fluid {
+        width: u32,
+        height: u32,
+        scale: f32,
+        #[serde(default)]
+        solver: SolverKind,
+    },
+    // path relative to assets/models
+    Obj {
+        path: String,
+        // recentre and scale the model to unit size, for models authored in other units
+        #[serde(default)]
+        normalize: bool,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityDesc {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub kind: EntityKind,
+    #[serde(default)]
+    pub transform: TransformDesc,
+    #[serde(default)]
+    pub look_at: Option<[f32; 3]>,
+    #[serde(default)]
+    pub material: Option<MaterialParams>,
+    #[serde(default)]
+    pub fluid: Option<FluidParams>,
+    #[serde(default)]
+    pub water: Option<WaterParams>,
+    // floats on any fluid below it
+    #[serde(default)]
+    pub floating: Option<FloatParams>,
+}
+
+// rotation is a quaternion stored as (x, y, z, w)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransformDesc {
+    #[serde(default)]
+    pub translation: [f32; 3],
+    #[serde(default = "default_rotation")]
+    pub rotation: [f32; 4],
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        TransformDesc::from(&Transform::identity())
+    }
+}
+
+impl<'a> From<&'a Transform> for TransformDesc {
+    fn from(t: &'a Transform) -> Self {
+        TransformDesc {
+            translation: t.translation.into(),
+            rotation: [t.rotation.v.x, t.rotation.v.y, t.rotation.v.z, t.rotation.s],
+            scale: t.scale.into(),
+        }
+    }
+}
+
+impl TransformDesc {
+    pub fn to_transform(&self) -> Transform {
+        let r = self.rotation;
+        Transform {
+            translation: self.translation.into(),
+            rotation: cgmath::Quaternion::new(r[3], r[0], r[1], r[2]),
+            scale: self.scale.into(),
+        }
+    }
+}
+
+impl SceneDesc {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        ron::de::from_reader(file).map_err(|e| invalid_data(e.to_string()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| invalid_data(e.to_string()))?;
+        std::fs::write(path, text)
+    }
+
+    // sets up lights, background and camera on the context and rig, and returns the populated world
+    pub fn build(&self, ctx: &mut RenderContext, rig: &mut CameraRig, random: &Random) -> BasicWorld {
+        ctx.background = self.background;
+        self.camera.apply(ctx, rig);
+        self.build_world(ctx, random)
+    }
+
+    // only the world, leaving the camera where it is; used when re-rolling the seed
+    pub fn build_world(&self, ctx: &mut RenderContext, random: &Random) -> BasicWorld {
+        let mut world = BasicWorld::new();
+        for light in &self.lights {
+            let id = world.create();
+            match *light {
+                LightDesc::Directional { direction, color } => {
+                    world.transforms.insert(id, Transform::identity());
+                    world.lights.insert(id, Light::Directional { direction, color });
+                }
+                LightDesc::Point { position, color, distance, decay } => {
+                    world.transforms.insert(id, Transform::from_translation(position.into()));
+                    world.lights.insert(id, Light::Point { color, distance, decay });
+                }
+            }
+        }
+        for (i, desc) in self.entities.iter().enumerate() {
+            let mut rng = random.stream(&desc.name.clone().unwrap_or_else(|| format!("#{}", i)));
+            let id = desc.kind.create(&mut world, ctx);
+            if let Some(fluid) = world.fluids.get_mut(id) {
+                fluid.randomize(&mut rng);
+            }
+            spawn(&mut world, id, desc);
+        }
+        world
+    }
+
+    // puts a captured state back onto a world that was rebuilt by the sketch it came from:
+    // background, camera, and each entity's transform and parameters. Entities are matched by
+    // name, unnamed ones by their order among the entities that describe themselves
+    pub fn restore(&self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig) {
+        ctx.background = self.background;
+        self.camera.apply(ctx, rig);
+        let described: Vec<EntityId> = world.iter()
+            .map(|(id, _)| id)
+            .filter(|&id| world.kinds.contains(id))
+            .collect();
+        for (i, desc) in self.entities.iter().enumerate() {
+            let id = match desc.name {
+                Some(ref name) => world.query(|e| e.name.as_ref() == Some(name)).first().cloned(),
+                None => described.get(i).cloned(),
+            };
+            let id = match id {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(transform) = world.transform_mut(id) {
+                *transform = desc.transform.to_transform();
+            }
+            if let Some(params) = desc.material {
+                world.set_material_params(id, params);
+            }
+            if let Some(ref params) = desc.fluid {
+                world.set_fluid_params(id, params.clone());
+            }
+            if let Some(params) = desc.water {
+                world.set_water_params(id, params);
+            }
+            match desc.floating {
+                Some(params) => world.buoyancy.insert(id, Buoyancy::new(params)),
+                None => world.buoyancy.remove(id),
+            };
+        }
+    }
+
+    // snapshot of the running scene; entities that cannot describe themselves are skipped
+    pub fn capture(world: &BasicWorld, ctx: &RenderContext, rig: &mut CameraRig, random: &Random) -> Self {
+        let lights = world.lights
+            .iter()
+            .map(|(id, light)| match light.to_world(world.world_matrix(id)) {
+                WorldLight::Directional(l) => LightDesc::Directional {
+                    direction: l.direction(),
+                    color: l.color(),
+                },
+                WorldLight::Point(l) => LightDesc::Point {
+                    position: l.position(),
+                    color: l.color(),
+                    distance: l.distance(),
+                    decay: l.decay(),
+                },
+            })
+            .collect();
+        let entities = world.iter()
+            .filter_map(|(id, entry)| {
+                world.kinds.get(id).map(|kind| EntityDesc {
+                    name: entry.name.clone(),
+                    tags: entry.tags.clone(),
+                    kind: kind.clone(),
+                    transform: world.transforms.get(id).map_or_else(TransformDesc::default, TransformDesc::from),
+                    look_at: None,
+                    material: world.material_params(id),
+                    fluid: world.fluid_params(id),
+                    water: world.water_params(id),
+                    floating: world.buoyancy.get(id).map(|b| b.params),
+                })
+            })
+            .collect();
+        SceneDesc {
+            seed: Some(random.seed()),
+            background: ctx.background,
+            camera: CameraDesc::capture(ctx, rig),
+            lights,
+            entities,
+        }
+    }
+}
+
+impl EntityKind {
+    // a new entity at the origin with the components of this kind
+    pub fn create(&self, world: &mut BasicWorld, ctx: &mut RenderContext) -> EntityId {
+        match *self {
+            EntityKind::Fluid { width, height, scale, solver } => {
+                world.add_fluid(FluidSurface::with_solver(ctx, width, height, scale, solver, FluidParams::default()))
+            }
+            EntityKind::Obj { ref path, normalize } => {
+                let mut renderable = Renderable::new(entity_obj::load_obj(ctx, path));
+                if normalize {
+                    renderable.normalize();
+                }
+                let id = world.create();
+                world.transforms.insert(id, Transform::identity());
+                world.renderables.insert(id, renderable);
+                world.colliders.insert(id, Collider::new());
+                world.kinds.insert(id, self.clone());
+                id
+            }
+        }
+    }
+}
+
+impl CameraDesc {
+    pub fn apply(&self, ctx: &mut RenderContext, rig: &mut CameraRig) {
+        rig.look_from(self.position.into(), self.target.into());
+        rig.mode = self.mode;
+        ctx.set_fov(self.fov);
+    }
+
+    pub fn capture(ctx: &RenderContext, rig: &mut CameraRig) -> Self {
+        let position = rig.camera(0.0).position;
+        CameraDesc {
+            mode: rig.mode,
+            position,
+            target: rig.target().into(),
+            fov: ctx.fov,
+        }
+    }
+}
+
+fn spawn(world: &mut BasicWorld, id: EntityId, desc: &EntityDesc) {
+    if let Some(transform) = world.transform_mut(id) {
+        *transform = desc.transform.to_transform();
+        if let Some(target) = desc.look_at {
+            transform.look_at(target.into(), cgmath::Vector3::new(0.0, 1.0, 0.0));
+        }
+    }
+    if let Some(params) = desc.material {
+        world.set_material_params(id, params);
+    }
+    if let Some(ref params) = desc.fluid {
+        world.set_fluid_params(id, params.clone());
+    }
+    if let Some(params) = desc.water {
+        world.set_water_params(id, params);
+    }
+    world.set_name(id, desc.name.clone());
+    for tag in &desc.tags {
+        world.add_tag(id, tag);
+    }
+    if let Some(params) = desc.floating {
+        world.buoyancy.insert(id, Buoyancy::new(params));
+    }
+}
+
+fn default_background() -> [f32; 4] {
+    [0.3, 0.3, 0.3, 1.0]
+}
+
+fn default_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
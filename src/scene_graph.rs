@@ -0,0 +1,109 @@
+use cgmath;
+use transform::Transform;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+pub struct Node {
+    pub local: Transform,
+    world: cgmath::Matrix4<f32>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        SceneGraph {
+            nodes: vec![Node {
+                local: Transform::identity(),
+                world: cgmath::Matrix4::from_scale(1.0),
+                parent: None,
+                children: Vec::new(),
+            }],
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn add_node(&mut self, parent: NodeId, local: Transform) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        let world = self.nodes[parent.0].world * local.matrix();
+        self.nodes.push(Node {
+            local,
+            world,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn local(&self, id: NodeId) -> &Transform {
+        &self.nodes[id.0].local
+    }
+
+    pub fn local_mut(&mut self, id: NodeId) -> &mut Transform {
+        &mut self.nodes[id.0].local
+    }
+
+    pub fn world_matrix(&self, id: NodeId) -> cgmath::Matrix4<f32> {
+        self.nodes[id.0].world
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    pub fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(node) = current {
+            if node == ancestor {
+                return true;
+            }
+            current = self.nodes[node.0].parent;
+        }
+        false
+    }
+
+    // returns false (and changes nothing) if it would create a cycle
+    pub fn set_parent(&mut self, id: NodeId, parent: NodeId) -> bool {
+        if id == self.root() || self.is_ancestor(id, parent) {
+            return false;
+        }
+        if let Some(old) = self.nodes[id.0].parent {
+            self.nodes[old.0].children.retain(|&c| c != id);
+        }
+        self.nodes[id.0].parent = Some(parent);
+        self.nodes[parent.0].children.push(id);
+        true
+    }
+
+    // recompute world matrices top-down from the root
+    pub fn update(&mut self) {
+        let root = self.root();
+        self.nodes[root.0].world = self.nodes[root.0].local.matrix();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            let world = self.nodes[id.0].world;
+            for i in 0..self.nodes[id.0].children.len() {
+                let child = self.nodes[id.0].children[i];
+                self.nodes[child.0].world = world * self.nodes[child.0].local.matrix();
+                stack.push(child);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use piston_window;
+use camera::CameraRig;
+use random::Random;
+use render::RenderContext;
+use world::BasicWorld;
+
+pub mod scene_sketch;
+pub mod orbit_lights;
+
+pub trait Sketch {
+    // fills an empty world; called again whenever the seed changes
+    fn setup(&mut self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig, random: &Random);
+    fn update(&mut self, world: &mut BasicWorld, dt: f64) {}
+    fn on_input(&mut self, world: &mut BasicWorld, event: &piston_window::Event) {}
+}
+
+pub struct SketchRegistry {
+    entries: Vec<(String, Box<Fn() -> Box<Sketch>>)>,
+}
+
+impl SketchRegistry {
+    pub fn new() -> Self {
+        SketchRegistry {
+            entries: Vec::new(),
+        }
+    }
+
+    // the built-in sketches
+    pub fn builtin() -> Self {
+        let mut registry = SketchRegistry::new();
+        registry.register("water", || {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scenes/default.ron");
+            Box::new(scene_sketch::SceneSketch::load(path).unwrap())
+        });
+        registry.register("orbit_lights", || Box::new(orbit_lights::OrbitLights::new()));
+        registry
+    }
+
+    // replaces a sketch of the same name; returns its index
+    pub fn register<F: 'static + Fn() -> Box<Sketch>>(&mut self, name: &str, factory: F) -> usize {
+        match self.index_of(name) {
+            Some(index) => {
+                self.entries[index].1 = Box::new(factory);
+                index
+            }
+            None => {
+                self.entries.push((name.to_string(), Box::new(factory)));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries.iter().map(|&(ref name, _)| name.clone()).collect()
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        &self.entries[index].0
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|&(ref n, _)| n == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn create(&self, index: usize) -> Box<Sketch> {
+        (self.entries[index].1)()
+    }
+}
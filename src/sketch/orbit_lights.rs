@@ -0,0 +1,91 @@
+use std;
+use cgmath;
+use piston_window::{self, Button, Key, PressEvent};
+use rand::Rng;
+use camera::CameraRig;
+use component::Light;
+use fluid::FluidSurface;
+use random::Random;
+use render::RenderContext;
+use sketch::Sketch;
+use transform::Transform;
+use world::{BasicWorld, EntityId};
+
+const LIGHT_COUNT: usize = 4;
+
+struct Orbit {
+    id: EntityId,
+    radius: f32,
+    height: f32,
+    angle: f64,
+    speed: f64,
+}
+
+// coloured point lights circling over the water; up/down change their speed
+pub struct OrbitLights {
+    orbits: Vec<Orbit>,
+    speed: f64,
+}
+
+impl OrbitLights {
+    pub fn new() -> Self {
+        OrbitLights {
+            orbits: Vec::new(),
+            speed: 1.0,
+        }
+    }
+}
+
+impl Sketch for OrbitLights {
+    fn setup(&mut self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig, random: &Random) {
+        ctx.background = [0.05, 0.05, 0.08, 1.0];
+        ctx.set_fov(60.0);
+        rig.look_from(cgmath::Vector3::new(0.0, 1.5, 2.5), cgmath::Vector3::new(0.0, 0.0, 0.0));
+
+        let mut water = FluidSurface::new(ctx, 200, 200, 2.0);
+        water.randomize(&mut random.stream("water"));
+        let id = world.add_fluid(water);
+        world.set_name(id, Some("water".to_string()));
+
+        let mut rng = random.stream("lights");
+        self.orbits.clear();
+        for i in 0..LIGHT_COUNT {
+            let id = world.create();
+            world.set_name(id, Some(format!("light{}", i)));
+            world.transforms.insert(id, Transform::identity());
+            world.lights.insert(id, Light::Point {
+                color: [rng.gen_range(0.2, 1.0), rng.gen_range(0.2, 1.0), rng.gen_range(0.2, 1.0)],
+                distance: 3.0,
+                decay: 2.0,
+            });
+            self.orbits.push(Orbit {
+                id,
+                radius: rng.gen_range(0.3, 1.0),
+                height: rng.gen_range(0.2, 0.8),
+                angle: rng.gen_range(0.0, 2.0 * std::f64::consts::PI),
+                speed: rng.gen_range(0.2, 1.0) * if rng.gen() { 1.0 } else { -1.0 },
+            });
+        }
+    }
+
+    fn update(&mut self, world: &mut BasicWorld, dt: f64) {
+        for orbit in self.orbits.iter_mut() {
+            orbit.angle += orbit.speed * self.speed * dt;
+            if let Some(transform) = world.transform_mut(orbit.id) {
+                transform.translation = cgmath::Vector3::new(
+                    orbit.radius * orbit.angle.cos() as f32,
+                    orbit.height,
+                    orbit.radius * orbit.angle.sin() as f32,
+                );
+            }
+        }
+    }
+
+    fn on_input(&mut self, _world: &mut BasicWorld, event: &piston_window::Event) {
+        match event.press_args() {
+            Some(Button::Keyboard(Key::Up)) => self.speed *= 1.5,
+            Some(Button::Keyboard(Key::Down)) => self.speed /= 1.5,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::io;
+use std::path::Path;
+use camera::CameraRig;
+use random::Random;
+use render::RenderContext;
+use scene::SceneDesc;
+use sketch::Sketch;
+use world::BasicWorld;
+
+// a sketch described entirely by a scene file
+pub struct SceneSketch {
+    pub scene: SceneDesc,
+}
+
+impl SceneSketch {
+    pub fn new(scene: SceneDesc) -> Self {
+        SceneSketch { scene }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        SceneDesc::load(path).map(SceneSketch::new)
+    }
+}
+
+impl Sketch for SceneSketch {
+    fn setup(&mut self, world: &mut BasicWorld, ctx: &mut RenderContext, rig: &mut CameraRig, random: &Random) {
+        *world = self.scene.build(ctx, rig, random);
+    }
+}
@@ -0,0 +1,74 @@
+use cgmath::{self, prelude::*};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn from_translation(translation: cgmath::Vector3<f32>) -> Self {
+        Transform {
+            translation,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn from_scale(scale: f32) -> Self {
+        Transform {
+            scale: cgmath::Vector3::new(scale, scale, scale),
+            ..Transform::identity()
+        }
+    }
+
+    pub fn translate(&mut self, offset: cgmath::Vector3<f32>) {
+        self.translation += offset;
+    }
+
+    // rotation about a world-space axis, applied after the current rotation
+    pub fn rotate(&mut self, axis: cgmath::Vector3<f32>, angle: cgmath::Rad<f32>) {
+        self.rotation = (cgmath::Quaternion::from_axis_angle(axis.normalize(), angle) * self.rotation).normalize();
+    }
+
+    pub fn set_uniform_scale(&mut self, scale: f32) {
+        self.scale = cgmath::Vector3::new(scale, scale, scale);
+    }
+
+    // orient the local +Z axis towards `target`; a no-op if the direction is degenerate
+    pub fn look_at(&mut self, target: cgmath::Vector3<f32>, up: cgmath::Vector3<f32>) {
+        let dir = target - self.translation;
+        if dir.magnitude2() < 1e-12 {
+            return;
+        }
+        let forward = dir.normalize();
+        let side = up.cross(forward);
+        if side.magnitude2() < 1e-12 {
+            return;
+        }
+        let side = side.normalize();
+        let up = forward.cross(side);
+        self.rotation = cgmath::Quaternion::from(cgmath::Matrix3::from_cols(side, up, forward));
+    }
+
+    // scale first, then rotate, then translate
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.translation)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
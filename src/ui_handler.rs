@@ -1,14 +1,18 @@
 extern crate find_folder;
 
 use conrod;
-use conrod::{widget, Colorable, Positionable, Widget};
+use conrod::{widget, Colorable, Labelable, Positionable, Sizeable, Widget};
 use std;
 use gfx;
 use gfx_device_gl;
 use piston_window::{UpdateEvent, Window, self, generic_event};
+use clock::{self, SimClock};
+use fluid::FluidParams;
+use rand;
 
 
-widget_ids!(struct Ids { text });
+widget_ids!(struct Ids { text, pause, step, time_scale, seed, reroll, sketch,
+                         wave_speed, viscosity, damping, height_scale, ambient_wave, boundary });
 
 pub struct UIHandler<'a> {
     ui: conrod::Ui,
@@ -16,6 +20,14 @@ pub struct UIHandler<'a> {
     image_map: conrod::image::Map<piston_window::Texture<gfx_device_gl::Resources>>,
     text_texture_cache: piston_window::Texture<gfx_device_gl::Resources>,
     glyph_cache: conrod::text::GlyphCache<'a>,
+    pub status: String,
+    // set when the user asks for a different seed; the caller rebuilds the scene
+    pub seed_request: Option<u64>,
+    seed_text: String,
+    // set when another sketch is picked; the caller switches to it
+    pub sketch_request: Option<usize>,
+    sketch_names: Vec<String>,
+    sketch_index: usize,
 }
 
 impl<'a> UIHandler<'a> {
@@ -53,10 +65,36 @@ impl<'a> UIHandler<'a> {
             image_map,
             text_texture_cache,
             glyph_cache,
+            status: String::new(),
+            seed_request: None,
+            seed_text: String::new(),
+            sketch_request: None,
+            sketch_names: Vec::new(),
+            sketch_index: 0,
         }
     }
 
-    pub fn update(&mut self, window: &mut piston_window::PistonWindow, event: &piston_window::Event) {
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed_text = seed.to_string();
+    }
+
+    pub fn set_sketches(&mut self, names: Vec<String>, index: usize) {
+        self.sketch_names = names;
+        self.sketch_index = index;
+    }
+
+    pub fn set_sketch(&mut self, index: usize) {
+        self.sketch_index = index;
+    }
+
+    // `fluid` gets a panel of sliders; returns true when one of them changed it
+    pub fn update(&mut self,
+                  window: &mut piston_window::PistonWindow,
+                  event: &piston_window::Event,
+                  sim_clock: &mut SimClock,
+                  mut fluid: Option<&mut FluidParams>,
+    ) -> bool {
+        let mut changed = false;
         let size = window.size();
         let (win_w, win_h) = (size.width as conrod::Scalar, size.height as conrod::Scalar);
         if let Some(e) = conrod::backend::piston::event::convert(event.clone(), win_w, win_h) {
@@ -65,12 +103,134 @@ impl<'a> UIHandler<'a> {
         event.update(|_| {
             let ui = &mut self.ui.set_widgets();
 
-            // "Hello World!" in the middle of the screen.
-            widget::Text::new("aaa")//&format!("{:.*}", 2, 1.0 / diff))
-                .middle_of(ui.window)
+            widget::Text::new(&self.status)
+                .top_left_with_margin_on(ui.window, 10.0)
                 .color(conrod::color::WHITE)
                 .font_size(16)
                 .set(self.ids.text, ui);
+
+            let label = if sim_clock.paused { "Resume" } else { "Pause" };
+            for _ in widget::Button::new()
+                .label(label)
+                .label_font_size(12)
+                .w_h(80.0, 24.0)
+                .bottom_left_with_margin_on(ui.window, 10.0)
+                .set(self.ids.pause, ui) {
+                sim_clock.toggle_pause();
+            }
+            for _ in widget::Button::new()
+                .label("Step")
+                .label_font_size(12)
+                .w_h(80.0, 24.0)
+                .right_from(self.ids.pause, 10.0)
+                .set(self.ids.step, ui) {
+                sim_clock.step();
+            }
+            let scale_label = format!("time x{:.2}", sim_clock.time_scale);
+            if let Some(scale) = widget::Slider::new(sim_clock.time_scale, clock::MIN_TIME_SCALE, clock::MAX_TIME_SCALE)
+                .label(&scale_label)
+                .label_font_size(12)
+                .w_h(160.0, 24.0)
+                .right_from(self.ids.step, 10.0)
+                .set(self.ids.time_scale, ui) {
+                sim_clock.set_time_scale(scale);
+            }
+
+            for event in widget::TextBox::new(&self.seed_text)
+                .font_size(12)
+                .w_h(160.0, 24.0)
+                .right_from(self.ids.time_scale, 10.0)
+                .set(self.ids.seed, ui) {
+                match event {
+                    widget::text_box::Event::Update(text) => self.seed_text = text,
+                    widget::text_box::Event::Enter => {
+                        if let Ok(seed) = self.seed_text.parse() {
+                            self.seed_request = Some(seed);
+                        }
+                    }
+                }
+            }
+            for _ in widget::Button::new()
+                .label("Re-roll")
+                .label_font_size(12)
+                .w_h(80.0, 24.0)
+                .right_from(self.ids.seed, 10.0)
+                .set(self.ids.reroll, ui) {
+                self.seed_request = Some(rand::random());
+            }
+
+            if let Some(index) = widget::DropDownList::new(&self.sketch_names, Some(self.sketch_index))
+                .label_font_size(12)
+                .w_h(160.0, 24.0)
+                .top_right_with_margin_on(ui.window, 10.0)
+                .set(self.ids.sketch, ui) {
+                if index != self.sketch_index {
+                    self.sketch_request = Some(index);
+                }
+            }
+
+            if let Some(ref mut params) = fluid {
+                let label = format!("wave speed {:.1}", params.wave_speed);
+                if let Some(value) = widget::Slider::new(params.wave_speed, 0.0, 80.0)
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.sketch, 40.0)
+                    .set(self.ids.wave_speed, ui) {
+                    params.wave_speed = value;
+                    changed = true;
+                }
+                let label = format!("viscosity {:.2}", params.viscosity);
+                if let Some(value) = widget::Slider::new(params.viscosity, 0.0, 20.0)
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.wave_speed, 10.0)
+                    .set(self.ids.viscosity, ui) {
+                    params.viscosity = value;
+                    changed = true;
+                }
+                let label = format!("damping {:.3}", params.damping);
+                if let Some(value) = widget::Slider::new(params.damping, 0.0, 1.0)
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.viscosity, 10.0)
+                    .set(self.ids.damping, ui) {
+                    params.damping = value;
+                    changed = true;
+                }
+                let label = format!("height {:.2}", params.height_scale);
+                if let Some(value) = widget::Slider::new(params.height_scale, 0.0, 1.0)
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.damping, 10.0)
+                    .set(self.ids.height_scale, ui) {
+                    params.height_scale = value;
+                    changed = true;
+                }
+                let label = format!("ambient {:.2}", params.ambient_wave);
+                if let Some(value) = widget::Slider::new(params.ambient_wave, 0.0, 4.0)
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.height_scale, 10.0)
+                    .set(self.ids.ambient_wave, ui) {
+                    params.ambient_wave = value;
+                    changed = true;
+                }
+                let label = format!("{:?} edges", params.boundary);
+                for _ in widget::Button::new()
+                    .label(&label)
+                    .label_font_size(12)
+                    .w_h(160.0, 24.0)
+                    .down_from(self.ids.ambient_wave, 10.0)
+                    .set(self.ids.boundary, ui) {
+                    params.boundary = params.boundary.next();
+                    changed = true;
+                }
+            }
         });
 
         //
@@ -105,6 +265,7 @@ impl<'a> UIHandler<'a> {
                     texture_from_image);
             }
         });
+        changed
     }
 
     pub fn draw(&mut self) {}
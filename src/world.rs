@@ -1,39 +1,695 @@
 use entity;
 use piston_window;
+use cgmath;
 use render;
+use mesh;
+use component::{Buoyancy, Collider, ComponentStore, Light, Obstacle, Renderable, Simulation, WorldLight};
+use scene_graph::{NodeId, SceneGraph};
+use scene::EntityKind;
+use transform::Transform;
+use animation::{Animation, AnimatedValue};
+use material::{MaterialParams, WaterParams};
+use fluid::{self, Disturbance, FluidParams, FluidSurface};
+
+// generational handle: a removed entity's id is never handed out again
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        EntityId { index, generation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
 
 pub trait World {
-    fn add_entity<T: 'static + entity::Entity>(&mut self, entity: Box<T>);
+    // `Entity` trait objects are stored through the legacy adapter component
+    fn add_entity<T: 'static + entity::Entity>(&mut self, entity: Box<T>) -> EntityId;
+    fn remove(&mut self, id: EntityId) -> Option<Box<entity::Entity>>;
+    fn contains(&self, id: EntityId) -> bool;
+    fn get(&self, id: EntityId) -> Option<&(entity::Entity + 'static)>;
+    fn get_mut(&mut self, id: EntityId) -> Option<&mut (entity::Entity + 'static)>;
+    fn find(&self, name: &str) -> Option<EntityId>;
+    fn ids(&self) -> Vec<EntityId>;
     fn draw(&mut self, renderer: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder, dt: f64);
     fn update(&mut self, dt: f64);
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn bounds(&self, id: EntityId) -> Option<mesh::Aabb> {
+        self.get(id).and_then(|e| e.bounds())
+    }
+    // world-space (point, normal) of every surface that wants a planar reflection
+    fn reflection_planes(&self) -> Vec<(EntityId, (cgmath::Vector3<f32>, cgmath::Vector3<f32>))> {
+        Vec::new()
+    }
+}
+
+pub struct EntityEntry {
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub node: Option<NodeId>,
+}
+
+struct Slot {
+    generation: u32,
+    entry: Option<EntityEntry>,
 }
 
 pub struct BasicWorld {
-    entities: Vec<Box<entity::Entity>>
+    pub scene: SceneGraph,
+    pub transforms: ComponentStore<Transform>,
+    pub renderables: ComponentStore<Renderable>,
+    pub lights: ComponentStore<Light>,
+    pub simulations: ComponentStore<Box<Simulation>>,
+    pub animations: ComponentStore<Animation>,
+    pub buoyancy: ComponentStore<Buoyancy>,
+    pub fluids: ComponentStore<FluidSurface>,
+    pub colliders: ComponentStore<Collider>,
+    // how to recreate the entity when the scene is saved
+    pub kinds: ComponentStore<EntityKind>,
+    pub legacy: ComponentStore<Box<entity::Entity>>,
+    // wait for background simulation at the end of every update, so systems reading
+    // it (floating entities) see the same state for the same steps; for offline runs
+    pub lockstep: bool,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
 }
 
 impl BasicWorld {
     pub fn new() -> Self {
         BasicWorld {
-            entities: Vec::new(),
+            scene: SceneGraph::new(),
+            transforms: ComponentStore::new(),
+            renderables: ComponentStore::new(),
+            lights: ComponentStore::new(),
+            simulations: ComponentStore::new(),
+            animations: ComponentStore::new(),
+            buoyancy: ComponentStore::new(),
+            fluids: ComponentStore::new(),
+            colliders: ComponentStore::new(),
+            kinds: ComponentStore::new(),
+            legacy: ComponentStore::new(),
+            lockstep: false,
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // an entity without components; attach them through the stores
+    pub fn create(&mut self) -> EntityId {
+        let entry = EntityEntry {
+            name: None,
+            tags: Vec::new(),
+            node: None,
+        };
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.entry = Some(entry);
+                EntityId { index, generation: slot.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, entry: Some(entry) });
+                EntityId { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    // a fluid surface at the origin, saved with the scene as the kind it describes
+    pub fn add_fluid(&mut self, fluid: FluidSurface) -> EntityId {
+        let id = self.create();
+        self.transforms.insert(id, Transform::identity());
+        self.kinds.insert(id, fluid.describe());
+        self.fluids.insert(id, fluid);
+        id
+    }
+
+    pub fn entry(&self, id: EntityId) -> Option<&EntityEntry> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entry.as_ref())
+    }
+
+    pub fn entry_mut(&mut self, id: EntityId) -> Option<&mut EntityEntry> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entry.as_mut())
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item=(EntityId, &'a EntityEntry)> + 'a {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let id = EntityId { index: i as u32, generation: slot.generation };
+                slot.entry.as_ref().map(|entry| (id, entry))
+            })
+    }
+
+    pub fn name(&self, id: EntityId) -> Option<&str> {
+        self.entry(id).and_then(|e| e.name.as_ref().map(|n| n.as_str()))
+    }
+
+    pub fn set_name(&mut self, id: EntityId, name: Option<String>) -> bool {
+        self.entry_mut(id).map(|e| e.name = name).is_some()
+    }
+
+    pub fn add_tag(&mut self, id: EntityId, tag: &str) -> bool {
+        self.entry_mut(id)
+            .map(|e| if !e.tags.iter().any(|t| t == tag) {
+                e.tags.push(tag.to_string());
+            })
+            .is_some()
+    }
+
+    pub fn remove_tag(&mut self, id: EntityId, tag: &str) -> bool {
+        self.entry_mut(id).map(|e| e.tags.retain(|t| t != tag)).is_some()
+    }
+
+    pub fn has_tag(&self, id: EntityId, tag: &str) -> bool {
+        self.entry(id).map_or(false, |e| e.tags.iter().any(|t| t == tag))
+    }
+
+    pub fn tagged(&self, tag: &str) -> Vec<EntityId> {
+        self.query(|e| e.tags.iter().any(|t| t == tag))
+    }
+
+    pub fn query<F: Fn(&EntityEntry) -> bool>(&self, predicate: F) -> Vec<EntityId> {
+        self.iter()
+            .filter(|&(_, e)| predicate(e))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    // places a component entity under a scene graph node; legacy entities keep their own transform
+    pub fn attach(&mut self, id: EntityId, node: Option<NodeId>) -> bool {
+        self.entry_mut(id).map(|e| e.node = node).is_some()
+    }
+
+    pub fn parent_matrix(&self, id: EntityId) -> cgmath::Matrix4<f32> {
+        self.entry(id)
+            .and_then(|e| e.node)
+            .map_or(cgmath::Matrix4::from_scale(1.0), |node| self.scene.world_matrix(node))
+    }
+
+    // world matrix of a component entity: scene graph node, then its own transform
+    pub fn world_matrix(&self, id: EntityId) -> cgmath::Matrix4<f32> {
+        let local = self.transforms.get(id).map_or(cgmath::Matrix4::from_scale(1.0), |t| t.matrix());
+        self.parent_matrix(id) * local
+    }
+
+    // legacy entities own their transform, component entities keep it in the store
+    pub fn transform_mut(&mut self, id: EntityId) -> Option<&mut Transform> {
+        if self.legacy.contains(id) {
+            return self.legacy.get_mut(id).map(|e| e.transform_mut());
+        }
+        self.transforms.get_mut(id)
+    }
+
+    pub fn material_params(&self, id: EntityId) -> Option<MaterialParams> {
+        if let Some(e) = self.legacy.get(id) {
+            return e.material_params();
+        }
+        if let Some(fluid) = self.fluids.get(id) {
+            return fluid.material_params();
+        }
+        self.renderables.get(id).and_then(|r| r.material_params())
+    }
+
+    pub fn set_material_params(&mut self, id: EntityId, params: MaterialParams) {
+        if let Some(e) = self.legacy.get_mut(id) {
+            e.set_material_params(params);
+        }
+        if let Some(fluid) = self.fluids.get_mut(id) {
+            fluid.set_material_params(params);
+        }
+        if let Some(r) = self.renderables.get_mut(id) {
+            r.set_material_params(params);
+        }
+    }
+
+    // see `Entity::edit_material_params`
+    pub fn edit_material_params(&mut self, id: EntityId, edit: &Fn(&mut MaterialParams)) {
+        if let Some(e) = self.legacy.get_mut(id) {
+            e.edit_material_params(edit);
+        }
+        if let Some(fluid) = self.fluids.get_mut(id) {
+            fluid.edit_material_params(edit);
+        }
+        if let Some(r) = self.renderables.get_mut(id) {
+            r.edit_material_params(edit);
+        }
+    }
+
+    pub fn fluid_params(&self, id: EntityId) -> Option<FluidParams> {
+        self.fluids.get(id).map(|fluid| fluid.params().clone())
+    }
+
+    pub fn set_fluid_params(&mut self, id: EntityId, params: FluidParams) {
+        if let Some(fluid) = self.fluids.get_mut(id) {
+            fluid.set_params(params);
+        }
+    }
+
+    pub fn water_params(&self, id: EntityId) -> Option<WaterParams> {
+        self.fluids.get(id).map(|fluid| fluid.water_params())
+    }
+
+    pub fn set_water_params(&mut self, id: EntityId, params: WaterParams) {
+        if let Some(fluid) = self.fluids.get_mut(id) {
+            fluid.set_water_params(params);
+        }
+    }
+
+    // waits for background simulation, see `FluidSurface::sync`
+    pub fn sync(&mut self) {
+        for (_, fluid) in self.fluids.iter_mut() {
+            fluid.sync();
+        }
+    }
+
+    pub fn find_fluid(&self) -> Option<EntityId> {
+        self.fluids.iter().next().map(|(id, _)| id)
+    }
+
+    // nearest fluid surface hit by a world-space ray
+    pub fn raycast(&self, ray: &mesh::Ray) -> Option<(EntityId, f32)> {
+        self.fluids
+            .iter()
+            .filter_map(|(id, fluid)| fluid.raycast(self.world_matrix(id), ray).map(|t| (id, t)))
+            .fold(None, |best, hit| match best {
+                Some((_, t)) if t <= hit.1 => best,
+                _ => Some(hit),
+            })
+    }
+
+    pub fn disturb(&mut self, id: EntityId, point: cgmath::Vector3<f32>, disturbance: &Disturbance) {
+        let model = self.world_matrix(id);
+        if let Some(fluid) = self.fluids.get_mut(id) {
+            fluid.disturb(model, point, disturbance);
+        }
+    }
+
+    fn apply_animated(&mut self, id: EntityId, value: AnimatedValue) {
+        match value {
+            AnimatedValue::Translation(v) => {
+                if let Some(t) = self.transform_mut(id) {
+                    t.translation = v;
+                }
+            }
+            AnimatedValue::Rotation(q) => {
+                if let Some(t) = self.transform_mut(id) {
+                    t.rotation = q;
+                }
+            }
+            AnimatedValue::Scale(v) => {
+                if let Some(t) = self.transform_mut(id) {
+                    t.scale = v;
+                }
+            }
+            AnimatedValue::Albedo(c) => self.edit_material_params(id, &|params: &mut MaterialParams| params.albedo = c),
+            AnimatedValue::Emissive(c) => self.edit_material_params(id, &|params: &mut MaterialParams| params.emissive = c),
+            AnimatedValue::Roughness(r) => self.edit_material_params(id, &|params: &mut MaterialParams| params.roughness = r),
+            AnimatedValue::LightColor(c) => {
+                match self.lights.get_mut(id) {
+                    Some(&mut Light::Directional { ref mut color, .. }) => *color = c,
+                    Some(&mut Light::Point { ref mut color, .. }) => *color = c,
+                    None => {}
+                }
+            }
+            AnimatedValue::LightDistance(d) => {
+                if let Some(&mut Light::Point { ref mut distance, .. }) = self.lights.get_mut(id) {
+                    *distance = d;
+                }
+            }
+        }
+    }
+
+    // slices every renderable with a collider against each fluid's rest plane;
+    // other renderables and legacy entities only contribute their bounds, and
+    // floating entities ride the surface instead of blocking it
+    fn obstacle_system(&mut self) {
+        let fluids: Vec<(EntityId, cgmath::Matrix4<f32>)> = self.fluids
+            .iter()
+            .map(|(id, _)| (id, self.world_matrix(id)))
+            .collect();
+        if fluids.is_empty() {
+            return;
+        }
+        let solids: Vec<(EntityId, cgmath::Matrix4<f32>)> = self.renderables
+            .iter()
+            .filter(|&(id, _)| !self.buoyancy.contains(id))
+            .map(|(id, _)| (id, self.world_matrix(id)))
+            .collect();
+        for (owner, model) in fluids {
+            let (point, normal) = fluid::surface::rest_plane(model);
+            let mut obstacles: Vec<Obstacle> = Vec::new();
+            for &(id, matrix) in &solids {
+                let renderable = match self.renderables.get(id) {
+                    Some(renderable) => renderable,
+                    None => continue,
+                };
+                match self.colliders.get_mut(id) {
+                    Some(collider) => obstacles.extend(collider.obstacle(renderable, matrix, point, normal)),
+                    None => obstacles.extend(renderable.local_bounds().map(|b| Obstacle::Bounds(b.transform(matrix)))),
+                }
+            }
+            let buoyancy = &self.buoyancy;
+            obstacles.extend(self.legacy
+                .iter()
+                .filter(|&(id, _)| !buoyancy.contains(id))
+                .filter_map(|(_, e)| e.bounds())
+                .map(Obstacle::Bounds));
+            if let Some(surface) = self.fluids.get_mut(owner) {
+                surface.set_obstacles(model, &obstacles);
+            }
+        }
+    }
+
+    fn animation_system(&mut self, dt: f64) {
+        let mut values = Vec::new();
+        for (id, animation) in self.animations.iter_mut() {
+            animation.advance(dt);
+            for value in animation.sample() {
+                values.push((id, value));
+            }
+        }
+        for (id, value) in values {
+            self.apply_animated(id, value);
+        }
+    }
+
+    // samples the first fluid under each floating entity's footprint: the
+    // corners of its bounds and their centre
+    fn buoyancy_system(&mut self, dt: f64) {
+        let ids: Vec<EntityId> = self.buoyancy.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            let bounds = match self.bounds(id) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            let (min, max, center) = (bounds.min, bounds.max, bounds.center());
+            let footprint = [
+                cgmath::Vector3::new(min.x, center.y, min.z),
+                cgmath::Vector3::new(max.x, center.y, min.z),
+                cgmath::Vector3::new(min.x, center.y, max.z),
+                cgmath::Vector3::new(max.x, center.y, max.z),
+                center,
+            ];
+            let (fluid, surface) = self.fluids
+                .iter()
+                .filter(|&(other, _)| other != id)
+                .map(|(other, f)| {
+                    let model = self.world_matrix(other);
+                    (Some(other), footprint.iter().filter_map(|&p| f.surface(model, p)).collect::<Vec<_>>())
+                })
+                .find(|&(_, ref surface)| !surface.is_empty())
+                .unwrap_or((None, Vec::new()));
+
+            let parent = self.parent_matrix(id);
+            let transform = match self.legacy.get_mut(id) {
+                Some(e) => e.transform_mut(),
+                None => match self.transforms.get_mut(id) {
+                    Some(transform) => transform,
+                    None => continue,
+                },
+            };
+            let (moved, wake) = match self.buoyancy.get_mut(id) {
+                Some(buoyancy) => (buoyancy.step(transform, parent, &surface, dt), buoyancy.params.wake),
+                None => continue,
+            };
+
+            // only movement across the surface leaves a wake, bobbing would feed itself
+            if let Some(fluid) = fluid {
+                if moved > 0.0 && wake > 0.0 {
+                    let radius = ((max.x - min.x).max(max.z - min.z) * 0.5).max(1e-3);
+                    let disturbance = Disturbance { strength: -wake * moved, radius };
+                    self.disturb(fluid, center, &disturbance);
+                }
+            }
         }
     }
+
+    fn simulation_system(&mut self, dt: f64) {
+        for (id, simulation) in self.simulations.iter_mut() {
+            if let Some(transform) = self.transforms.get_mut(id) {
+                simulation.step(transform, dt);
+            }
+        }
+    }
+
+    fn fluid_system(&mut self, dt: f64) {
+        for (_, fluid) in self.fluids.iter_mut() {
+            fluid.update(dt);
+        }
+    }
+
+    // adapter: legacy entities run their own update
+    fn legacy_update_system(&mut self, dt: f64) {
+        for (_, e) in self.legacy.iter_mut() {
+            e.update(dt);
+        }
+    }
+
+    fn light_system(&self, renderer: &mut render::RenderContext) {
+        renderer.directional_lights.clear();
+        renderer.point_lights.clear();
+        for (id, light) in self.lights.iter() {
+            match light.to_world(self.world_matrix(id)) {
+                WorldLight::Directional(l) => renderer.directional_lights.push(l),
+                WorldLight::Point(l) => renderer.point_lights.push(l),
+            }
+        }
+    }
+
+    fn render_system(&mut self, renderer: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder) {
+        let matrices: Vec<(EntityId, cgmath::Matrix4<f32>)> = self.renderables
+            .iter()
+            .map(|(id, _)| (id, self.world_matrix(id)))
+            .collect();
+        for (id, model) in matrices {
+            if renderer.pass == render::Pass::Reflection(id) {
+                continue;
+            }
+            renderer.drawing = Some(id);
+            if let Some(renderable) = self.renderables.get_mut(id) {
+                for part in renderable.parts.iter_mut() {
+                    part.draw(renderer, encoder, model);
+                }
+            }
+        }
+        renderer.drawing = None;
+    }
+
+    // a surface is left out of its own reflection
+    fn fluid_render_system(&mut self, renderer: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder) {
+        let matrices: Vec<(EntityId, cgmath::Matrix4<f32>)> = self.fluids
+            .iter()
+            .map(|(id, _)| (id, self.world_matrix(id)))
+            .collect();
+        for (id, model) in matrices {
+            if renderer.pass == render::Pass::Reflection(id) {
+                continue;
+            }
+            renderer.drawing = Some(id);
+            if let Some(fluid) = self.fluids.get_mut(id) {
+                fluid.draw(renderer, encoder, model);
+            }
+        }
+        renderer.drawing = None;
+    }
 }
 
 impl World for BasicWorld {
-    fn add_entity<T: 'static + entity::Entity>(&mut self, entity: Box<T>) {
-        self.entities.push(entity);
+    fn add_entity<T: 'static + entity::Entity>(&mut self, entity: Box<T>) -> EntityId {
+        let id = self.create();
+        self.legacy.insert(id, entity);
+        id
+    }
+
+    fn remove(&mut self, id: EntityId) -> Option<Box<entity::Entity>> {
+        let removed = match self.slots.get_mut(id.index as usize) {
+            Some(ref mut slot) if slot.generation == id.generation && slot.entry.is_some() => {
+                slot.entry = None;
+                true
+            }
+            _ => false,
+        };
+        if !removed {
+            return None;
+        }
+        self.transforms.remove(id);
+        self.renderables.remove(id);
+        self.lights.remove(id);
+        self.simulations.remove(id);
+        self.animations.remove(id);
+        self.buoyancy.remove(id);
+        self.fluids.remove(id);
+        self.colliders.remove(id);
+        self.kinds.remove(id);
+        let entity = self.legacy.remove(id);
+        self.slots[id.index as usize].generation = id.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        entity
+    }
+
+    fn contains(&self, id: EntityId) -> bool {
+        self.entry(id).is_some()
+    }
+
+    fn get(&self, id: EntityId) -> Option<&(entity::Entity + 'static)> {
+        self.legacy.get(id).map(|e| &**e)
+    }
+
+    fn get_mut(&mut self, id: EntityId) -> Option<&mut (entity::Entity + 'static)> {
+        self.legacy.get_mut(id).map(|e| &mut **e)
+    }
+
+    fn find(&self, name: &str) -> Option<EntityId> {
+        self.iter()
+            .find(|&(_, e)| e.name.as_ref().map_or(false, |n| n == name))
+            .map(|(id, _)| id)
+    }
+
+    fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _)| id).collect()
     }
 
     fn draw(&mut self, renderer: &mut render::RenderContext, encoder: &mut piston_window::GfxEncoder, dt: f64) {
-        for i in self.entities.iter_mut() {
-            i.draw(renderer, encoder, dt)
+        self.light_system(renderer);
+        self.render_system(renderer, encoder);
+        self.fluid_render_system(renderer, encoder);
+        for (id, e) in self.legacy.iter_mut() {
+            if renderer.pass == render::Pass::Reflection(id) {
+                continue;
+            }
+            renderer.drawing = Some(id);
+            e.draw(renderer, encoder, dt)
         }
+        renderer.drawing = None;
+    }
+
+    // reflecting fluid surfaces, plus the tops of entities tagged "mirror"
+    fn reflection_planes(&self) -> Vec<(EntityId, (cgmath::Vector3<f32>, cgmath::Vector3<f32>))> {
+        let mut planes: Vec<_> = self.fluids
+            .iter()
+            .filter_map(|(id, fluid)| fluid.reflection_plane(self.world_matrix(id)).map(|plane| (id, plane)))
+            .collect();
+        for id in self.tagged("mirror") {
+            if planes.iter().any(|&(other, _)| other == id) {
+                continue;
+            }
+            if let Some(bounds) = self.bounds(id) {
+                let center = bounds.center();
+                planes.push((id, (cgmath::Vector3::new(center.x, bounds.max.y, center.z), cgmath::Vector3::unit_y())));
+            }
+        }
+        planes
     }
 
     fn update(&mut self, dt: f64) {
-        for mut i in &mut self.entities {
-            i.update(dt);
+        self.scene.update();
+        self.simulation_system(dt);
+        self.animation_system(dt);
+        self.buoyancy_system(dt);
+        self.obstacle_system();
+        self.fluid_system(dt);
+        self.legacy_update_system(dt);
+        if self.lockstep {
+            self.sync();
         }
     }
-}
\ No newline at end of file
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn bounds(&self, id: EntityId) -> Option<mesh::Aabb> {
+        if let Some(e) = self.legacy.get(id) {
+            return e.bounds();
+        }
+        let local = match self.fluids.get(id) {
+            Some(fluid) => fluid.local_bounds(),
+            None => self.renderables.get(id).and_then(|r| r.local_bounds()),
+        };
+        local.map(|b| b.transform(self.world_matrix(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_ids_stay_dead_after_their_slot_is_reused() {
+        let mut world = BasicWorld::new();
+        let old = world.create();
+        world.transforms.insert(old, Transform::identity());
+        world.kinds.insert(old, EntityKind::Obj { path: "old.obj".to_string(), normalize: false });
+        world.set_name(old, Some("old".to_string()));
+        world.remove(old);
+        assert!(!world.contains(old));
+
+        let new = world.create();
+        assert_eq!(new.index(), old.index());
+        assert!(new.generation() != old.generation());
+        world.transforms.insert(new, Transform::identity());
+
+        assert!(!world.contains(old));
+        assert!(world.entry(old).is_none());
+        assert!(world.entry_mut(old).is_none());
+        assert!(!world.transforms.contains(old));
+        assert!(world.transforms.get(old).is_none());
+        // nothing the old entity was made of carries over to the new one
+        assert!(!world.kinds.contains(new));
+        assert!(world.find("old").is_none());
+        assert!(world.contains(new));
+        assert!(world.entry(new).map_or(false, |e| e.name.is_none()));
+        assert!(world.transforms.contains(new));
+        // a stale id can't remove the entity now living in its slot
+        assert!(world.remove(old).is_none());
+        assert!(world.contains(new));
+    }
+
+    #[test]
+    fn len_and_free_slots_follow_create_and_remove() {
+        let mut world = BasicWorld::new();
+        let a = world.create();
+        let b = world.create();
+        let c = world.create();
+        assert_eq!(world.len(), 3);
+
+        world.remove(b);
+        world.remove(b);
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.ids(), vec![a, c]);
+
+        world.remove(a);
+        // freed slots are reused last freed first before the slot list grows
+        let d = world.create();
+        let e = world.create();
+        let f = world.create();
+        assert_eq!((d.index(), e.index(), f.index()), (a.index(), b.index(), 3));
+        assert_eq!(world.len(), 4);
+        assert_eq!(world.ids().len(), 4);
+        assert!(!world.is_empty());
+    }
+}